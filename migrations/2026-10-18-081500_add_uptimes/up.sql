@@ -0,0 +1,10 @@
+CREATE TABLE uptimes (
+    id INTEGER NOT NULL PRIMARY KEY,
+    resolution VARCHAR(4) NOT NULL,
+    period TIMESTAMP NOT NULL,
+    checks INTEGER NOT NULL DEFAULT 0,
+    up INTEGER NOT NULL DEFAULT 0,
+    instance_id INTEGER NOT NULL,
+    FOREIGN KEY(instance_id) REFERENCES instances(id) ON DELETE CASCADE,
+    UNIQUE(instance_id, resolution, period)
+);
@@ -0,0 +1,15 @@
+ALTER TABLE checks RENAME TO _checks;
+
+CREATE TABLE checks (
+    id INTEGER NOT NULL PRIMARY KEY,
+    updated TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    up BOOLEAN NOT NULL DEFAULT 0,
+    instance_id INTEGER NOT NULL,
+    FOREIGN KEY(instance_id) REFERENCES instances(id) ON DELETE CASCADE
+);
+
+INSERT INTO checks (id, updated, up, instance_id)
+SELECT id, updated, up, instance_id
+FROM _checks;
+
+DROP TABLE _checks;
@@ -0,0 +1,4 @@
+ALTER TABLE checks
+ADD latency INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE checks
+ADD status INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,16 @@
+ALTER TABLE scans RENAME TO _scans;
+
+CREATE TABLE scans (
+    id INTEGER NOT NULL PRIMARY KEY,
+    scanner VARCHAR(255) NOT NULL,
+    rating VARCHAR(255) NOT NULL DEFAULT "-",
+    percent INTEGER NOT NULL DEFAULT 0,
+    instance_id INTEGER NOT NULL,
+    FOREIGN KEY(instance_id) REFERENCES instances(id) ON DELETE CASCADE
+);
+
+INSERT INTO scans (id, scanner, rating, percent, instance_id)
+SELECT id, scanner, rating, percent, instance_id
+FROM _scans;
+
+DROP TABLE _scans;
@@ -0,0 +1,2 @@
+ALTER TABLE scans
+ADD details TEXT NOT NULL DEFAULT "{}";
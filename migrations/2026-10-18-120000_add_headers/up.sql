@@ -0,0 +1,8 @@
+CREATE TABLE headers (
+    id INTEGER NOT NULL PRIMARY KEY,
+    name VARCHAR(64) NOT NULL,
+    passed BOOLEAN NOT NULL DEFAULT 0,
+    instance_id INTEGER NOT NULL,
+    FOREIGN KEY(instance_id) REFERENCES instances(id) ON DELETE CASCADE,
+    UNIQUE(instance_id, name)
+);
@@ -0,0 +1,9 @@
+ALTER TABLE instances ADD discussion BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE instances ADD password BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE instances ADD burn_after_reading BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE instances ADD qr_code BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE instances ADD expiration_options VARCHAR(255) NOT NULL DEFAULT "";
+ALTER TABLE instances ADD expiration_default VARCHAR(16) NOT NULL DEFAULT "";
+ALTER TABLE instances ADD formatters VARCHAR(255) NOT NULL DEFAULT "";
+ALTER TABLE instances ADD formatter_default VARCHAR(32) NOT NULL DEFAULT "";
+ALTER TABLE instances ADD languages VARCHAR(255) NOT NULL DEFAULT "";
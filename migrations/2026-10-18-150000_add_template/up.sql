@@ -0,0 +1 @@
+ALTER TABLE instances ADD template VARCHAR(32) NOT NULL DEFAULT "";
@@ -0,0 +1,9 @@
+CREATE TABLE instance_events (
+    id INTEGER NOT NULL PRIMARY KEY,
+    updated TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    field VARCHAR(32) NOT NULL,
+    old_value VARCHAR(255) NOT NULL,
+    new_value VARCHAR(255) NOT NULL,
+    instance_id INTEGER NOT NULL,
+    FOREIGN KEY(instance_id) REFERENCES instances(id) ON DELETE CASCADE
+);
@@ -0,0 +1,32 @@
+ALTER TABLE instances RENAME TO _instances;
+
+CREATE TABLE instances (
+    id INTEGER NOT NULL PRIMARY KEY,
+    url VARCHAR(255) NOT NULL UNIQUE,
+    version VARCHAR(255) NOT NULL,
+    https BOOLEAN NOT NULL DEFAULT 0,
+    https_redirect BOOLEAN NOT NULL DEFAULT 0,
+    country_id CHARACTER(2) NOT NULL DEFAULT "AQ",
+    attachments BOOLEAN NOT NULL DEFAULT 0,
+    csp_header BOOLEAN NOT NULL DEFAULT 0,
+    discussion BOOLEAN NOT NULL DEFAULT 0,
+    password BOOLEAN NOT NULL DEFAULT 0,
+    burn_after_reading BOOLEAN NOT NULL DEFAULT 0,
+    qr_code BOOLEAN NOT NULL DEFAULT 0,
+    expiration_options VARCHAR(255) NOT NULL DEFAULT "",
+    expiration_default VARCHAR(16) NOT NULL DEFAULT "",
+    formatters VARCHAR(255) NOT NULL DEFAULT "",
+    formatter_default VARCHAR(32) NOT NULL DEFAULT "",
+    languages VARCHAR(255) NOT NULL DEFAULT "",
+    template VARCHAR(32) NOT NULL DEFAULT ""
+);
+
+INSERT INTO instances (id, url, version, https, https_redirect, country_id, attachments,
+    csp_header, discussion, password, burn_after_reading, qr_code, expiration_options,
+    expiration_default, formatters, formatter_default, languages, template)
+SELECT id, url, version, https, https_redirect, country_id, attachments, csp_header,
+    discussion, password, burn_after_reading, qr_code, expiration_options, expiration_default,
+    formatters, formatter_default, languages, template
+FROM _instances;
+
+DROP TABLE _instances;
@@ -0,0 +1,3 @@
+ALTER TABLE instances ADD status VARCHAR(16) NOT NULL DEFAULT "active";
+ALTER TABLE instances ADD status_changed TIMESTAMP;
+ALTER TABLE instances ADD failures INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,5 @@
+ALTER TABLE instances ADD verified BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE instances ADD delisted BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE instances ADD display_name VARCHAR(64) NOT NULL DEFAULT "";
+ALTER TABLE instances ADD description VARCHAR(255) NOT NULL DEFAULT "";
+ALTER TABLE instances ADD contact VARCHAR(255) NOT NULL DEFAULT "";
@@ -0,0 +1,18 @@
+ALTER TABLE instances ADD pinned BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE instances ADD hidden BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE instances ADD notes TEXT NOT NULL DEFAULT "";
+
+CREATE TABLE blocklist (
+    id INTEGER NOT NULL PRIMARY KEY,
+    domain VARCHAR(255) NOT NULL UNIQUE,
+    reason VARCHAR(255) NOT NULL DEFAULT "",
+    created TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE audit_log (
+    id INTEGER NOT NULL PRIMARY KEY,
+    created TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    action VARCHAR(32) NOT NULL,
+    target VARCHAR(255) NOT NULL,
+    details TEXT NOT NULL DEFAULT ""
+);
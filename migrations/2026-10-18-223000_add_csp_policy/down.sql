@@ -0,0 +1,46 @@
+ALTER TABLE instances RENAME TO _instances;
+
+CREATE TABLE instances (
+    id INTEGER NOT NULL PRIMARY KEY,
+    url VARCHAR(255) NOT NULL UNIQUE,
+    version VARCHAR(255) NOT NULL,
+    https BOOLEAN NOT NULL DEFAULT 0,
+    https_redirect BOOLEAN NOT NULL DEFAULT 0,
+    country_id CHARACTER(2) NOT NULL DEFAULT "AQ",
+    attachments BOOLEAN NOT NULL DEFAULT 0,
+    csp_header BOOLEAN NOT NULL DEFAULT 0,
+    discussion BOOLEAN NOT NULL DEFAULT 0,
+    password BOOLEAN NOT NULL DEFAULT 0,
+    burn_after_reading BOOLEAN NOT NULL DEFAULT 0,
+    qr_code BOOLEAN NOT NULL DEFAULT 0,
+    expiration_options VARCHAR(255) NOT NULL DEFAULT "",
+    expiration_default VARCHAR(16) NOT NULL DEFAULT "",
+    formatters VARCHAR(255) NOT NULL DEFAULT "",
+    formatter_default VARCHAR(32) NOT NULL DEFAULT "",
+    languages VARCHAR(255) NOT NULL DEFAULT "",
+    template VARCHAR(32) NOT NULL DEFAULT "",
+    status VARCHAR(16) NOT NULL DEFAULT "active",
+    status_changed TIMESTAMP,
+    failures INTEGER NOT NULL DEFAULT 0,
+    verified BOOLEAN NOT NULL DEFAULT 0,
+    delisted BOOLEAN NOT NULL DEFAULT 0,
+    display_name VARCHAR(64) NOT NULL DEFAULT "",
+    description VARCHAR(255) NOT NULL DEFAULT "",
+    contact VARCHAR(255) NOT NULL DEFAULT "",
+    pinned BOOLEAN NOT NULL DEFAULT 0,
+    hidden BOOLEAN NOT NULL DEFAULT 0,
+    notes TEXT NOT NULL DEFAULT ""
+);
+
+INSERT INTO instances (id, url, version, https, https_redirect, country_id, attachments,
+    csp_header, discussion, password, burn_after_reading, qr_code, expiration_options,
+    expiration_default, formatters, formatter_default, languages, template, status,
+    status_changed, failures, verified, delisted, display_name, description, contact, pinned,
+    hidden, notes)
+SELECT id, url, version, https, https_redirect, country_id, attachments, csp_header,
+    discussion, password, burn_after_reading, qr_code, expiration_options, expiration_default,
+    formatters, formatter_default, languages, template, status, status_changed, failures,
+    verified, delisted, display_name, description, contact, pinned, hidden, notes
+FROM _instances;
+
+DROP TABLE _instances;
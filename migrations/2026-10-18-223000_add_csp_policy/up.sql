@@ -0,0 +1 @@
+ALTER TABLE instances ADD csp_policy TEXT NOT NULL DEFAULT "";
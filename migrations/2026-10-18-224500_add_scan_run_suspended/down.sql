@@ -0,0 +1,17 @@
+ALTER TABLE scan_runs RENAME TO _scan_runs;
+
+CREATE TABLE scan_runs (
+    id INTEGER NOT NULL PRIMARY KEY,
+    started TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished TIMESTAMP,
+    checked INTEGER NOT NULL DEFAULT 0,
+    updated INTEGER NOT NULL DEFAULT 0,
+    removed INTEGER NOT NULL DEFAULT 0,
+    failed INTEGER NOT NULL DEFAULT 0
+);
+
+INSERT INTO scan_runs (id, started, finished, checked, updated, removed, failed)
+SELECT id, started, finished, checked, updated, removed + suspended, failed
+FROM _scan_runs;
+
+DROP TABLE _scan_runs;
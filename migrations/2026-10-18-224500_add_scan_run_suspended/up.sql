@@ -0,0 +1 @@
+ALTER TABLE scan_runs ADD suspended INTEGER NOT NULL DEFAULT 0;
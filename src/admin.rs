@@ -0,0 +1,478 @@
+// moderation of the listed instances, authenticated by the admin token of the configuration
+use super::functions::strip_url;
+use super::models::{DirectoryConfig, DirectoryDbConn, TITLE, sha256_hex};
+use super::schema::{audit_log, blocklist, instances};
+use super::tasks::rescan_instance;
+use super::{InstancesCache, Relaxed};
+use diesel::prelude::*;
+use diesel::{SqliteConnection, delete, insert_into, update};
+use ring::rand::{SecureRandom, SystemRandom};
+use rocket::form::Form;
+use rocket::http::{Cookie, CookieJar, Status};
+use rocket::request::{FlashMessage, FromRequest, Outcome, Request};
+use rocket::response::{Flash, Redirect};
+use rocket::serde::Serialize;
+use rocket::{Route, State};
+use rocket_dyn_templates::Template;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::sync::RwLock;
+use url::Url;
+
+const ADMIN_COOKIE: &str = "admin";
+const ADMIN_TITLE: &str = "Moderation";
+const AUDIT_ENTRIES: i64 = 50; // most recent moderation actions to show
+const SESSION_BYTES: usize = 32; // random bytes of the session IDs
+
+/// IDs of the sessions of logged in moderators, kept in memory, so the admin section doesn't
+/// require a secret key for private cookies - a restart, i.e. to change the token, ends them
+#[derive(Default)]
+pub struct AdminSessions(RwLock<HashSet<String>>);
+
+/// request guard, only succeeding for moderators that logged in with the current admin token
+pub struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let (Some(_), Some(sessions)) = (
+            admin_token(request.rocket().state::<DirectoryConfig>()),
+            request.rocket().state::<AdminSessions>(),
+        ) else {
+            return Outcome::Forward(Status::NotFound);
+        };
+        match request.cookies().get(ADMIN_COOKIE) {
+            Some(cookie)
+                if sessions
+                    .0
+                    .read()
+                    .is_ok_and(|sessions| sessions.contains(cookie.value())) =>
+            {
+                Outcome::Success(Admin)
+            }
+            _ => Outcome::Forward(Status::Unauthorized),
+        }
+    }
+}
+
+fn admin_token(config: Option<&DirectoryConfig>) -> Option<&str> {
+    config.and_then(|config| config.admin_token.as_deref())
+}
+
+#[derive(FromForm)]
+pub struct LoginForm {
+    pub token: String,
+}
+
+#[derive(FromForm)]
+pub struct ModerationForm {
+    pub notes: String,
+    pub pinned: bool,
+    pub hidden: bool,
+}
+
+#[derive(FromForm)]
+pub struct BlocklistForm {
+    pub domain: String,
+    pub reason: String,
+}
+
+#[derive(Queryable, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ModeratedInstance {
+    pub id: i32,
+    pub url: String,
+    pub status: String,
+    pub delisted: bool,
+    pub pinned: bool,
+    pub hidden: bool,
+    pub notes: String,
+}
+
+#[derive(Queryable, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct BlockedDomain {
+    pub id: i32,
+    pub domain: String,
+    pub reason: String,
+    pub created: String,
+}
+
+#[derive(Queryable, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AuditEntry {
+    pub created: String,
+    pub action: String,
+    pub target: String,
+    pub details: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = audit_log)]
+pub struct AuditEntryNew<'a> {
+    pub action: &'a str,
+    pub target: &'a str,
+    pub details: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AdminPage {
+    pub title: String,
+    pub topic: String,
+    pub error: String,
+    pub success: String,
+    pub authenticated: bool,
+    pub instances: Vec<ModeratedInstance>,
+    pub blocklist: Vec<BlockedDomain>,
+    pub audit_log: Vec<AuditEntry>,
+}
+
+impl AdminPage {
+    #[must_use]
+    pub fn new(authenticated: bool, flash: Option<FlashMessage<'_>>) -> AdminPage {
+        let (error, success) = match flash {
+            Some(flash) if flash.kind() == "error" => (flash.message().into(), String::new()),
+            Some(flash) => (String::new(), flash.message().into()),
+            None => (String::new(), String::new()),
+        };
+        AdminPage {
+            title: TITLE.into(),
+            topic: ADMIN_TITLE.into(),
+            error,
+            success,
+            authenticated,
+            instances: vec![],
+            blocklist: vec![],
+            audit_log: vec![],
+        }
+    }
+}
+
+#[must_use]
+pub fn routes() -> Vec<Route> {
+    routes![
+        admin,
+        admin_login_form,
+        login,
+        logout,
+        moderate,
+        rescan,
+        remove,
+        block,
+        unblock
+    ]
+}
+
+/// the blocked domain, if the host of the URL is it or one of its subdomains
+///
+/// # Errors
+///
+/// Will return `Err` if the blocklist can't be queried.
+pub fn blocked_domain(conn: &mut SqliteConnection, url: &str) -> QueryResult<Option<String>> {
+    let Some(host) = Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+    else {
+        return Ok(None);
+    };
+    let domains: Vec<String> = blocklist::table.select(blocklist::domain).load(conn)?;
+    Ok(domains
+        .into_iter()
+        .find(|domain| host == *domain || host.ends_with(&format!(".{domain}"))))
+}
+
+// record a moderation action
+fn audit(
+    conn: &mut SqliteConnection,
+    action: &str,
+    target: &str,
+    details: &str,
+) -> QueryResult<usize> {
+    insert_into(audit_log::table)
+        .values(AuditEntryNew {
+            action,
+            target,
+            details,
+        })
+        .execute(conn)
+}
+
+fn instance_url(conn: &mut SqliteConnection, instance_id: i32) -> QueryResult<String> {
+    instances::table
+        .select(instances::url)
+        .filter(instances::id.eq(instance_id))
+        .first(conn)
+}
+
+// flash the outcome of an action on the dashboard
+fn outcome(result: QueryResult<String>, cache: &InstancesCache) -> Flash<Redirect> {
+    match result {
+        Ok(message) => {
+            cache.timeout.store(0, Relaxed);
+            Flash::success(Redirect::to("/admin"), message)
+        }
+        Err(e) => Flash::error(
+            Redirect::to("/admin"),
+            format!("Action failed, due to: {e}"),
+        ),
+    }
+}
+
+#[get("/")]
+async fn admin(_admin: Admin, db: DirectoryDbConn, flash: Option<FlashMessage<'_>>) -> Template {
+    let mut page = AdminPage::new(true, flash);
+    let (moderated_instances, blocked_domains, audit_entries) = db
+        .run(|conn| {
+            (
+                instances::table
+                    .select((
+                        instances::id,
+                        instances::url,
+                        instances::status,
+                        instances::delisted,
+                        instances::pinned,
+                        instances::hidden,
+                        instances::notes,
+                    ))
+                    .order(instances::url.asc())
+                    .load::<ModeratedInstance>(conn)
+                    .unwrap_or_default(),
+                blocklist::table
+                    .select((
+                        blocklist::id,
+                        blocklist::domain,
+                        blocklist::reason,
+                        blocklist::created,
+                    ))
+                    .order(blocklist::domain.asc())
+                    .load::<BlockedDomain>(conn)
+                    .unwrap_or_default(),
+                audit_log::table
+                    .select((
+                        audit_log::created,
+                        audit_log::action,
+                        audit_log::target,
+                        audit_log::details,
+                    ))
+                    .order(audit_log::id.desc())
+                    .limit(AUDIT_ENTRIES)
+                    .load::<AuditEntry>(conn)
+                    .unwrap_or_default(),
+            )
+        })
+        .await;
+    page.instances = moderated_instances;
+    page.blocklist = blocked_domains;
+    page.audit_log = audit_entries;
+    Template::render("admin", page)
+}
+
+#[get("/", rank = 2)]
+fn admin_login_form(
+    config: &State<DirectoryConfig>,
+    flash: Option<FlashMessage<'_>>,
+) -> Option<Template> {
+    admin_token(Some(config))?;
+    Some(Template::render("admin", AdminPage::new(false, flash)))
+}
+
+#[post("/login", data = "<form>")]
+fn login(
+    form: Form<LoginForm>,
+    cookies: &CookieJar<'_>,
+    config: &State<DirectoryConfig>,
+    sessions: &State<AdminSessions>,
+) -> Option<Flash<Redirect>> {
+    let token = admin_token(Some(config))?;
+    let form = form.into_inner();
+    // comparing the hashes doesn't leak the length or the matching prefix of the token
+    if sha256_hex(&form.token) != sha256_hex(token) {
+        return Some(Flash::error(Redirect::to("/admin"), "Invalid admin token."));
+    }
+    let mut random = [0u8; SESSION_BYTES];
+    if SystemRandom::new().fill(&mut random).is_err() {
+        return Some(Flash::error(
+            Redirect::to("/admin"),
+            "Failed to start a session, please try again.",
+        ));
+    }
+    let session = random.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    });
+    if let Ok(mut sessions) = sessions.0.write() {
+        sessions.insert(session.clone());
+    }
+    cookies.add(Cookie::build((ADMIN_COOKIE, session)).path("/admin"));
+    Some(Flash::success(Redirect::to("/admin"), "Logged in."))
+}
+
+#[post("/logout")]
+fn logout(cookies: &CookieJar<'_>, sessions: &State<AdminSessions>) -> Redirect {
+    if let Some(cookie) = cookies.get(ADMIN_COOKIE)
+        && let Ok(mut sessions) = sessions.0.write()
+    {
+        sessions.remove(cookie.value());
+    }
+    cookies.remove(Cookie::build(ADMIN_COOKIE).path("/admin"));
+    Redirect::to("/admin")
+}
+
+#[post("/instance/<instance_id>", data = "<form>")]
+async fn moderate(
+    _admin: Admin,
+    instance_id: i32,
+    form: Form<ModerationForm>,
+    db: DirectoryDbConn,
+    cache: &State<InstancesCache>,
+) -> Flash<Redirect> {
+    let form = form.into_inner();
+    let result = db
+        .run(move |conn| {
+            conn.transaction(|conn| {
+                let url = instance_url(conn, instance_id)?;
+                let notes = form.notes.trim();
+                update(instances::table.filter(instances::id.eq(instance_id)))
+                    .set((
+                        instances::notes.eq(notes),
+                        instances::pinned.eq(form.pinned),
+                        instances::hidden.eq(form.hidden),
+                    ))
+                    .execute(conn)?;
+                audit(
+                    conn,
+                    "moderate",
+                    &url,
+                    &format!(
+                        "pinned: {}, hidden: {}, notes: {notes}",
+                        form.pinned, form.hidden
+                    ),
+                )?;
+                Ok(format!("Instance {url} updated."))
+            })
+        })
+        .await;
+    outcome(result, cache)
+}
+
+#[post("/instance/<instance_id>/rescan")]
+async fn rescan(
+    _admin: Admin,
+    instance_id: i32,
+    db: DirectoryDbConn,
+    cache: &State<InstancesCache>,
+) -> Flash<Redirect> {
+    let Some(message) = rescan_instance(&db, instance_id).await else {
+        return Flash::error(Redirect::to("/admin"), "Instance not found.");
+    };
+    let details = message.clone();
+    let result = db
+        .run(move |conn| {
+            let url = instance_url(conn, instance_id).unwrap_or_default();
+            audit(conn, "rescan", &url, &details)
+        })
+        .await
+        .map(|_| message);
+    outcome(result, cache)
+}
+
+#[post("/instance/<instance_id>/delete")]
+async fn remove(
+    _admin: Admin,
+    instance_id: i32,
+    db: DirectoryDbConn,
+    cache: &State<InstancesCache>,
+) -> Flash<Redirect> {
+    let result = db
+        .run(move |conn| {
+            let url = instance_url(conn, instance_id)?;
+            conn.transaction(|conn| {
+                delete(instances::table.filter(instances::id.eq(instance_id))).execute(conn)?;
+                audit(conn, "delete", &url, "")?;
+                Ok(format!("Instance {url} deleted."))
+            })
+        })
+        .await;
+    outcome(result, cache)
+}
+
+#[post("/blocklist", data = "<form>")]
+async fn block(
+    _admin: Admin,
+    form: Form<BlocklistForm>,
+    db: DirectoryDbConn,
+    cache: &State<InstancesCache>,
+) -> Flash<Redirect> {
+    let form = form.into_inner();
+    let domain = form
+        .domain
+        .trim()
+        .trim_start_matches("*.")
+        .trim_end_matches('.')
+        .to_ascii_lowercase();
+    if domain.is_empty() || domain.contains(['/', ':', ' ']) {
+        return Flash::error(
+            Redirect::to("/admin"),
+            "Please enter a domain, without scheme or path.",
+        );
+    }
+    let result = db
+        .run(move |conn| {
+            let reason = form.reason.trim();
+            // instances of the domain get deleted along with the listing
+            let blocked_instances: Vec<(i32, String)> = instances::table
+                .select((instances::id, instances::url))
+                .load::<(i32, String)>(conn)?
+                .into_iter()
+                .filter(|(_, url)| {
+                    Url::parse(&strip_url(url.clone())).is_ok_and(|url| {
+                        url.host_str().is_some_and(|host| {
+                            host == domain || host.ends_with(&format!(".{domain}"))
+                        })
+                    })
+                })
+                .collect();
+            conn.transaction(|conn| {
+                insert_into(blocklist::table)
+                    .values((blocklist::domain.eq(&domain), blocklist::reason.eq(reason)))
+                    .execute(conn)?;
+                audit(conn, "block", &domain, reason)?;
+                for (instance_id, url) in &blocked_instances {
+                    delete(instances::table.filter(instances::id.eq(instance_id))).execute(conn)?;
+                    audit(conn, "delete", url, &format!("domain {domain} blocked"))?;
+                }
+                Ok(format!(
+                    "Domain {domain} blocked, {} instances deleted.",
+                    blocked_instances.len()
+                ))
+            })
+        })
+        .await;
+    outcome(result, cache)
+}
+
+#[post("/blocklist/<domain_id>/delete")]
+async fn unblock(
+    _admin: Admin,
+    domain_id: i32,
+    db: DirectoryDbConn,
+    cache: &State<InstancesCache>,
+) -> Flash<Redirect> {
+    let result = db
+        .run(move |conn| {
+            conn.transaction(|conn| {
+                let domain: String = blocklist::table
+                    .select(blocklist::domain)
+                    .filter(blocklist::id.eq(domain_id))
+                    .first(conn)?;
+                delete(blocklist::table.filter(blocklist::id.eq(domain_id))).execute(conn)?;
+                audit(conn, "unblock", &domain, "")?;
+                Ok(format!("Domain {domain} unblocked."))
+            })
+        })
+        .await;
+    outcome(result, cache)
+}
@@ -1,3 +1,5 @@
+use super::models::DirectoryError;
+use super::robots::PRODUCT_TOKEN;
 use http_body_util::Full;
 use hyper::Uri;
 use hyper::body::{Bytes, Incoming};
@@ -7,13 +9,23 @@ use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
 use hyper_util::client::legacy::Client;
 use hyper_util::client::legacy::connect::HttpConnector;
 use hyper_util::rt::TokioExecutor;
-use std::sync::OnceLock;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
 use std::time::Duration;
-use tokio::time::timeout;
+use tokio::time::{Instant, sleep_until, timeout};
 use url::{Position, Url};
 
 static HTTP_CLIENT: OnceLock<Client<HttpsConnector<HttpConnector>, Full<Bytes>>> = OnceLock::new();
-const TIMEOUT_SECONDS: u64 = 25;
+pub const TIMEOUT_SECONDS: u64 = 25; // default, unless configured otherwise
+static REQUEST_TIMEOUT: AtomicU64 = AtomicU64::new(TIMEOUT_SECONDS);
+#[cfg(not(test))]
+pub const HOST_DELAY_MS: u64 = 1000; // politeness delay between requests to the same host
+#[cfg(test)]
+pub const HOST_DELAY_MS: u64 = 0; // no delay, for unit tests
+pub const REQUESTS_PER_SECOND: u32 = 20; // budget shared by all probes, 0 for unlimited
+static RATE_LIMITER: RwLock<Option<Arc<RateLimiter>>> = RwLock::new(None);
+const MAX_TRACKED_HOSTS: usize = 1024;
 
 // cache frequently used header values
 pub static CLOSE: HeaderValue = HeaderValue::from_static("close");
@@ -33,13 +45,34 @@ pub async fn request(
     method: Method,
     connection: &HeaderValue,
     body: Bytes,
-) -> Result<Response<Incoming>, String> {
+) -> Result<Response<Incoming>, DirectoryError> {
+    timed_request(url, method, connection, body).await.0
+}
+
+/// like `request`, but also returns how long the request took, excluding the wait for the rate
+/// limits, i.e. to measure the latency of the web server
+///
+/// # Panics
+///
+/// May panic in `Request::builder().[...].unwrap()`.
+pub async fn timed_request(
+    url: &str,
+    method: Method,
+    connection: &HeaderValue,
+    body: Bytes,
+) -> (Result<Response<Incoming>, DirectoryError>, Duration) {
     // parse URL to convert IDN into punycode
     let Ok(parsed_url) = Url::parse(url) else {
-        return Err(format!("Host or domain of URL {url} is not supported."));
+        return (
+            Err(DirectoryError::UnsupportedHost(url.into())),
+            Duration::ZERO,
+        );
     };
     let Some(parsed_host) = parsed_url.host_str() else {
-        return Err(format!("Unable to parse host from URL {url}."));
+        return (
+            Err(DirectoryError::UnsupportedHost(url.into())),
+            Duration::ZERO,
+        );
     };
     let authority = match parsed_url.port() {
         Some(port) => format!("{parsed_host}:{port}"),
@@ -51,9 +84,14 @@ pub async fn request(
         .path_and_query(&parsed_url[Position::BeforePath..])
         .build()
     else {
-        return Err(format!("Host or domain of URL {url} is not supported."));
+        return (
+            Err(DirectoryError::UnsupportedHost(url.into())),
+            Duration::ZERO,
+        );
     };
 
+    throttle(parsed_host).await;
+    let timer = Instant::now();
     let request = Request::builder()
         .method(method)
         .uri(parsed_uri)
@@ -63,7 +101,7 @@ pub async fn request(
             USER_AGENT_VALUE.get_or_init(|| {
                 HeaderValue::from_static(USER_AGENT_STRING.get_or_init(|| {
                     format!(
-                        "PrivateBinDirectoryBot/{} (+https://privatebin.info/directory/about)",
+                        "{PRODUCT_TOKEN}/{} (+https://privatebin.info/directory/about)",
                         env!("CARGO_PKG_VERSION")
                     )
                 }))
@@ -71,8 +109,9 @@ pub async fn request(
         )
         .body(Full::from(body))
         .unwrap();
-    match timeout(
-        Duration::from_secs(TIMEOUT_SECONDS),
+    let timeout_seconds = timeout_seconds();
+    let result = match timeout(
+        Duration::from_secs(timeout_seconds),
         HTTP_CLIENT
             .get_or_init(init_connection)
             .clone()
@@ -82,35 +121,119 @@ pub async fn request(
     {
         Ok(result) => match result {
             Ok(result) => Ok(result),
-            Err(_) => Err(format!("Web server on URL {url} is not responding.")),
+            Err(_) => Err(DirectoryError::Unreachable(url.into())),
         },
-        Err(_) => Err(format!(
-            "Web server on URL {url} is not responding within {TIMEOUT_SECONDS}s."
-        )),
-    }
+        Err(_) => Err(DirectoryError::Timeout(url.into(), timeout_seconds)),
+    };
+    (result, timer.elapsed())
 }
 
 /// # Errors
 ///
 /// Will return `Err` if request to `url` fails for any reason.
-pub async fn request_get(url: &str) -> Result<Response<Incoming>, String> {
+pub async fn request_get(url: &str) -> Result<Response<Incoming>, DirectoryError> {
     request(url, Method::GET, &KEEPALIVE, Bytes::new()).await
 }
 
 /// # Errors
 ///
 /// Will return `Err` if request to `url` fails for any reason.
-pub async fn request_head(url: &str) -> Result<Response<Incoming>, String> {
+pub async fn request_head(url: &str) -> Result<Response<Incoming>, DirectoryError> {
     request(url, Method::HEAD, &KEEPALIVE, Bytes::new()).await
 }
 
 /// # Errors
 ///
 /// Will return `Err` if request to `url` fails for any reason.
-pub async fn request_post(url: &str) -> Result<Response<Incoming>, String> {
+pub async fn request_post(url: &str) -> Result<Response<Incoming>, DirectoryError> {
     request(url, Method::POST, &KEEPALIVE, Bytes::new()).await
 }
 
+/// seconds to wait for web servers to respond
+#[must_use]
+pub fn timeout_seconds() -> u64 {
+    REQUEST_TIMEOUT.load(Relaxed)
+}
+
+pub fn set_timeout_seconds(seconds: u64) {
+    REQUEST_TIMEOUT.store(seconds, Relaxed);
+}
+
+/// spaces out requests, both to the same host and overall
+pub struct RateLimiter {
+    host_delay: Duration,
+    interval: Duration,
+    next_request: Mutex<Instant>,
+    next_host_requests: Mutex<HashMap<String, Instant>>,
+}
+
+impl RateLimiter {
+    #[must_use]
+    pub fn new(host_delay_ms: u64, requests_per_second: u32) -> RateLimiter {
+        RateLimiter {
+            host_delay: Duration::from_millis(host_delay_ms),
+            interval: if requests_per_second == 0 {
+                Duration::ZERO
+            } else {
+                Duration::from_secs(1) / requests_per_second
+            },
+            next_request: Mutex::new(Instant::now()),
+            next_host_requests: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// wait for the next free slot to send a request to the given host
+    pub async fn wait(&self, host: &str) {
+        if !self.host_delay.is_zero() {
+            let slot = {
+                let now = Instant::now();
+                let mut next_host_requests = self
+                    .next_host_requests
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner);
+                if next_host_requests.len() >= MAX_TRACKED_HOSTS {
+                    next_host_requests.retain(|_, next| *next > now);
+                }
+                let slot = next_host_requests
+                    .get(host)
+                    .map_or(now, |next| (*next).max(now));
+                next_host_requests.insert(host.into(), slot + self.host_delay);
+                slot
+            };
+            sleep_until(slot).await;
+        }
+        if !self.interval.is_zero() {
+            let slot = {
+                let mut next_request = self
+                    .next_request
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner);
+                let slot = (*next_request).max(Instant::now());
+                *next_request = slot + self.interval;
+                slot
+            };
+            sleep_until(slot).await;
+        }
+    }
+}
+
+pub fn set_rate_limits(host_delay_ms: u64, requests_per_second: u32) {
+    if let Ok(mut rate_limiter) = RATE_LIMITER.write() {
+        *rate_limiter = Some(Arc::new(RateLimiter::new(
+            host_delay_ms,
+            requests_per_second,
+        )));
+    }
+}
+
+/// wait until the configured rate limits permit probing the given host
+pub async fn throttle(host: &str) {
+    let rate_limiter = RATE_LIMITER.read().ok().and_then(|limiter| limiter.clone());
+    if let Some(rate_limiter) = rate_limiter {
+        rate_limiter.wait(host).await;
+    }
+}
+
 #[must_use]
 pub fn init_connection() -> Client<HttpsConnector<HttpConnector>, Full<Bytes>> {
     let https_connector = HttpsConnectorBuilder::new()
@@ -121,3 +244,23 @@ pub fn init_connection() -> Client<HttpsConnector<HttpConnector>, Full<Bytes>> {
         .build();
     Client::builder(TokioExecutor::new()).build(https_connector)
 }
+
+#[tokio::test]
+async fn test_rate_limiter() {
+    let rate_limiter = RateLimiter::new(200, 20);
+    let start = Instant::now();
+    rate_limiter.wait("privatebin.example").await;
+    rate_limiter.wait("zerobin.example").await;
+    // requests to different hosts are only spaced by the global budget
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert!(start.elapsed() < Duration::from_millis(200));
+    rate_limiter.wait("privatebin.example").await;
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    let unlimited = RateLimiter::new(0, 0);
+    let start = Instant::now();
+    for _ in 0..10 {
+        unlimited.wait("privatebin.example").await;
+    }
+    assert!(start.elapsed() < Duration::from_millis(50));
+}
@@ -1,26 +1,35 @@
+use super::connections::{set_rate_limits, set_timeout_seconds};
+use super::models::{InstanceEventNew, InstanceStatus};
+use super::scanners::set_observatory_api;
 use super::{
-    Build, CRON_INTERVAL, DirectoryDbConn, Instance, InstancesCache, Relaxed, Rocket, State,
-    Template, about, add, api, check, favicon, forward_me, index, report, save,
+    Build, DirectoryConfig, DirectoryDbConn, Instance, InstancesCache, Relaxed, Rocket, State,
+    Template, about, add, admin, api, api_check, api_events, api_openapi, api_uptime,
+    api_v2_instances, check, favicon, forward_me, index, instance, operator, operator_save, report,
+    save,
 };
 use diesel::prelude::*;
 use diesel::query_builder::SqlQuery;
+use diesel::{SqliteConnection, insert_into, update};
 use diesel_migrations::EmbeddedMigrations;
 use diesel_migrations::MigrationHarness;
 use regex::Regex;
+use rocket::figment::Figment;
 use rocket::fs::FileServer;
+use rocket::http::RawStr;
+use rocket::http::uri::Origin;
 use rocket_dyn_templates::tera::{Result, Value, to_value, try_get_value};
 use std::collections::HashMap;
 use std::sync::OnceLock;
 use std::sync::RwLock;
-use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicI64, AtomicU64};
 
 // 1F1E6 is the unicode code point for the "REGIONAL INDICATOR SYMBOL
 // LETTER A" and 41 is the one for A in unicode and ASCII
 const REGIONAL_INDICATOR_OFFSET: u32 = 0x1F1E6 - 0x41;
 #[cfg(not(test))]
-const CACHE_TIMEOUT: u64 = 300; // 5 minutes
+pub const CACHE_TIMEOUT: u64 = 300; // 5 minutes
 #[cfg(test)]
-const CACHE_TIMEOUT: u64 = 1; // 1 second, for unit tests
+pub const CACHE_TIMEOUT: u64 = 1; // 1 second, for unit tests
 static SLASHES_EXP: OnceLock<Regex> = OnceLock::new();
 
 /// # Panics
@@ -37,32 +46,125 @@ pub fn get_epoch() -> u64 {
         .as_secs()
 }
 
+/// instances listed in the directory, that is active and degraded ones, unless their operators
+/// requested them to get delisted or a moderator hid them
 pub fn get_instances() -> SqlQuery {
-    diesel::dsl::sql_query(
+    instances_query(&format!(
+        "instances.status IN ('{}', '{}') AND NOT delisted AND NOT hidden",
+        InstanceStatus::Active.as_str(),
+        InstanceStatus::Degraded.as_str()
+    ))
+}
+
+/// all instances that get checked, including the suspended and removed ones, so they can get
+/// re-activated if they come back
+pub fn get_monitored_instances() -> SqlQuery {
+    instances_query("1")
+}
+
+/// a single monitored instance, i.e. to rescan it on demand
+pub fn get_monitored_instance(instance_id: i32) -> SqlQuery {
+    instances_query(&format!("instances.id = {instance_id}"))
+}
+
+fn instances_query(condition: &str) -> SqlQuery {
+    // latency percentiles use the nearest-rank method, over the successful checks
+    diesel::dsl::sql_query(format!(
         "SELECT instances.id, url, version, https, https_redirect, country_id, \
-            attachments, csp_header, (100 * SUM(checks.up) / COUNT(checks.up)) AS uptime, \
-            mozilla_observatory.rating AS rating_mozilla_observatory \
+            country_id AS location, \
+            attachments, csp_header, csp_policy, discussion, password, burn_after_reading, qr_code, \
+            expiration_options, expiration_default, formatters, formatter_default, languages, \
+            template, instances.status, failures, verified, display_name, description, contact, \
+            (100 * SUM(checks.up) / COUNT(checks.up)) AS uptime, \
+            COALESCE(latencies.p50, 0) AS latency_p50, COALESCE(latencies.p95, 0) AS latency_p95, \
+            mozilla_observatory.rating AS rating_mozilla_observatory, \
+            COALESCE(ratings.ratings, '{{}}') AS ratings, \
+            CAST((NULLIF(ratings.cert_not_after, 0) - strftime('%s', 'now')) / 86400 \
+                AS INTEGER) AS cert_days, \
+            COALESCE(header_checks.headers, '{{}}') AS headers \
             FROM instances \
             JOIN checks ON instances.id = checks.instance_id \
             JOIN ( \
                 SELECT rating, percent, instance_id \
                 FROM scans WHERE scanner = \"mozilla_observatory\" \
             ) AS mozilla_observatory ON instances.id = mozilla_observatory.instance_id \
+            LEFT JOIN ( \
+                SELECT instance_id, \
+                MIN(CASE WHEN 100 * position >= 50 * total THEN latency END) AS p50, \
+                MIN(CASE WHEN 100 * position >= 95 * total THEN latency END) AS p95 \
+                FROM ( \
+                    SELECT instance_id, latency, \
+                    ROW_NUMBER() OVER (PARTITION BY instance_id ORDER BY latency) AS position, \
+                    COUNT(*) OVER (PARTITION BY instance_id) AS total \
+                    FROM checks WHERE up = 1 AND latency > 0 \
+                ) GROUP BY instance_id \
+            ) AS latencies ON instances.id = latencies.instance_id \
+            LEFT JOIN ( \
+                SELECT instance_id, json_group_object(scanner, rating) AS ratings, \
+                MAX(CASE WHEN scanner = 'tls' THEN json_extract(details, '$.not_after') END) \
+                    AS cert_not_after \
+                FROM scans GROUP BY instance_id \
+            ) AS ratings ON instances.id = ratings.instance_id \
+            LEFT JOIN ( \
+                SELECT instance_id, \
+                json_group_object(name, json(CASE WHEN passed THEN 'true' ELSE 'false' END)) \
+                    AS headers \
+                FROM headers GROUP BY instance_id \
+            ) AS header_checks ON instances.id = header_checks.instance_id \
+            WHERE {condition} \
             GROUP BY instances.id \
-            ORDER BY version DESC, https DESC, https_redirect DESC, csp_header DESC, \
+            ORDER BY version DESC, pinned DESC, https DESC, https_redirect DESC, csp_header DESC, \
             mozilla_observatory.percent DESC, attachments DESC, uptime DESC, url ASC \
-            LIMIT 1000",
-    )
+            LIMIT 1000"
+    ))
+}
+
+/// change the status of an instance and record the transition as an event, both or neither
+///
+/// # Errors
+///
+/// Will return `Err` if the instance couldn't be updated, i.e. as it no longer exists, or the
+/// event couldn't be stored.
+pub fn set_instance_status(
+    conn: &mut SqliteConnection,
+    instance_id: i32,
+    old_status: InstanceStatus,
+    new_status: InstanceStatus,
+) -> QueryResult<()> {
+    use super::schema::instance_events::dsl::instance_events;
+    use super::schema::instances::dsl::{id, instances, status, status_changed};
+
+    conn.transaction(|conn| {
+        let count = update(instances.filter(id.eq(instance_id)))
+            .set((
+                status.eq(new_status.as_str()),
+                status_changed.eq(diesel::dsl::now.nullable()),
+            ))
+            .execute(conn)?;
+        if count == 0 {
+            return Err(diesel::result::Error::NotFound);
+        }
+        insert_into(instance_events)
+            .values(InstanceEventNew {
+                field: "status".into(),
+                old_value: old_status.as_str().into(),
+                new_value: new_status.as_str().into(),
+                instance_id,
+            })
+            .execute(conn)?;
+        Ok(())
+    })
 }
 
 pub fn is_cached<S: std::hash::BuildHasher>(
     cache: &RwLock<HashMap<String, u64, S>>,
     key: &str,
+    timeout: u64,
 ) -> bool {
     if let Ok(read_cache) = cache.read()
         && let Some(timestamp) = read_cache.get(key)
     {
-        if *timestamp < get_epoch() - CACHE_TIMEOUT {
+        if *timestamp < get_epoch().saturating_sub(timeout) {
             drop(read_cache); // drop read lock, before requesting a write one
             if let Ok(mut write_cache) = cache.write() {
                 write_cache.remove(key);
@@ -74,36 +176,110 @@ pub fn is_cached<S: std::hash::BuildHasher>(
     false
 }
 
+// see https://en.wikipedia.org/wiki/Academic_grading_in_the_United_States#Numerical_and_letter_grades
+pub const RATINGS: [(&str, u8); 13] = [
+    ("A+", 97),
+    ("A", 93),
+    ("A-", 90),
+    ("B+", 87),
+    ("B", 83),
+    ("B-", 80),
+    ("C+", 77),
+    ("C", 73),
+    ("C-", 70),
+    ("D+", 67),
+    ("D", 63),
+    ("D-", 60),
+    ("F", 50),
+];
+
 #[must_use]
 pub fn rating_to_percent(rating: &str) -> u8 {
-    // see https://en.wikipedia.org/wiki/Academic_grading_in_the_United_States#Numerical_and_letter_grades
-    match rating {
-        "A+" => 97,
-        "A" => 93,
-        "A-" => 90,
-        "B+" => 87,
-        "B" => 83,
-        "B-" => 80,
-        "C+" => 77,
-        "C" => 73,
-        "C-" => 70,
-        "D+" => 67,
-        "D" => 63,
-        "D-" => 60,
-        "F" => 50,
-        _ => 0,
+    RATINGS
+        .iter()
+        .find(|(name, _)| *name == rating)
+        .map_or(0, |(_, percent)| *percent)
+}
+
+#[must_use]
+pub fn expiry_to_seconds(expiry: &str) -> Option<u64> {
+    // PrivateBin expiration options look like "5min", "1week" or "never"
+    if expiry == "never" {
+        return Some(u64::MAX);
+    }
+    let unit_start = expiry.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = expiry.split_at(unit_start);
+    let seconds = match unit.trim_end_matches('s') {
+        "sec" => 1,
+        "min" => 60,
+        "hour" => 3_600,
+        "day" => 86_400,
+        "week" => 604_800,
+        "month" => 2_592_000,
+        "year" => 31_536_000,
+        _ => return None,
+    };
+    amount.parse::<u64>().ok()?.checked_mul(seconds)
+}
+
+/// duration in the largest unit it is a multiple of, i.e. "15 minutes" for 900 seconds
+#[must_use]
+pub fn format_duration(seconds: u64) -> String {
+    let (amount, unit) = [(86_400, "day"), (3_600, "hour"), (60, "minute")]
+        .into_iter()
+        .find(|(length, _)| seconds >= *length && seconds.is_multiple_of(*length))
+        .map_or((seconds, "second"), |(length, unit)| {
+            (seconds / length, unit)
+        });
+    if amount == 1 {
+        format!("{amount} {unit}")
+    } else {
+        format!("{amount} {unit}s")
     }
 }
 
+/// # Panics
+///
+/// May panic if the directory section of the configuration is invalid.
 #[must_use]
 pub fn rocket() -> Rocket<Build> {
-    rocket::build()
+    rocket_with(rocket::Config::figment())
+}
+
+/// the web service, configured by the given figment instead of the default providers
+///
+/// # Panics
+///
+/// May panic if the directory section of the configuration is invalid.
+#[must_use]
+pub fn rocket_with(figment: Figment) -> Rocket<Build> {
+    let rocket = rocket::custom(figment);
+    let config = DirectoryConfig::from_figment(rocket.figment())
+        .unwrap_or_else(|message| panic!("{message}"));
+    rocket
         .mount(
             "/",
             routes![
-                about, add, api, check, favicon, forward_me, index, report, save
+                about,
+                add,
+                api,
+                api_check,
+                api_events,
+                api_openapi,
+                api_uptime,
+                api_v2_instances,
+                check,
+                favicon,
+                forward_me,
+                index,
+                instance,
+                operator,
+                operator_save,
+                report,
+                save
             ],
         )
+        .mount("/admin", admin::routes())
         .mount("/img", FileServer::from("img"))
         .mount("/css", FileServer::from("css"))
         .attach(DirectoryDbConn::fairing())
@@ -112,9 +288,22 @@ pub fn rocket() -> Rocket<Build> {
         }))
         .manage(InstancesCache {
             timeout: AtomicU64::new(0),
+            last_full_check: AtomicI64::new(0),
             instances: RwLock::new(vec![]),
             negative_lookups: RwLock::new(HashMap::new()),
         })
+        .manage(admin::AdminSessions::default())
+        .manage(config)
+}
+
+/// apply the configured timeout, rate limits and observatory API to the probes of instances,
+/// which share them within the process - so only the service itself sets them, not its tests
+pub fn configure_probes(config: &DirectoryConfig) {
+    set_timeout_seconds(config.timeout_seconds);
+    set_rate_limits(config.host_delay_ms, config.requests_per_second);
+    if let Some(observatory_api) = &config.observatory_api {
+        set_observatory_api(observatory_api.clone());
+    }
 }
 
 /// # Panics
@@ -171,13 +360,57 @@ pub fn strip_url(url: String) -> String {
     check_url
 }
 
-pub async fn update_instance_cache(db: DirectoryDbConn, cache: &State<InstancesCache>) {
+/// relative link repeating the query of the request, with the given parameters replaced and
+/// blank ones, as sent by empty form fields, dropped
+#[must_use]
+pub fn replace_query(uri: &Origin<'_>, params: &[(&str, &str)]) -> String {
+    let mut query: Vec<String> = uri
+        .query()
+        .map(|query| {
+            query
+                .raw_segments()
+                .map(RawStr::as_str)
+                .filter(|segment| {
+                    let (key, value) = segment.split_once('=').unwrap_or((segment, ""));
+                    !value.is_empty() && params.iter().all(|(name, _)| *name != key)
+                })
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default();
+    query.extend(params.iter().map(|(name, value)| format!("{name}={value}")));
+    format!("?{}", query.join("&"))
+}
+
+pub async fn update_instance_cache(
+    db: &DirectoryDbConn,
+    cache: &State<InstancesCache>,
+    config: &DirectoryConfig,
+) {
+    use super::schema::scan_runs::dsl::scan_runs;
+    use diesel::dsl::sql;
+    use diesel::sql_types::{BigInt, Nullable};
+
     let now = get_epoch();
     if now >= cache.timeout.load(Relaxed) {
-        match db.run(|conn| get_instances().load::<Instance>(conn)).await {
+        match db
+            .run(|conn| {
+                let instances = get_instances().load::<Instance>(conn)?;
+                let last_full_check = scan_runs
+                    .select(sql::<Nullable<BigInt>>(
+                        "CAST(strftime('%s', MAX(finished)) AS INTEGER)",
+                    ))
+                    .first::<Option<i64>>(conn)?;
+                QueryResult::Ok((instances, last_full_check))
+            })
+            .await
+        {
             // flush cache
-            Ok(instances_live) => {
-                cache.timeout.store(now + CRON_INTERVAL, Relaxed);
+            Ok((instances_live, last_full_check)) => {
+                cache.timeout.store(now + config.cron_interval, Relaxed);
+                cache
+                    .last_full_check
+                    .store(last_full_check.unwrap_or_default(), Relaxed);
                 if let Ok(mut instances_cache) = cache.instances.write() {
                     *instances_cache = instances_live;
                 }
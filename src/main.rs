@@ -12,112 +12,250 @@ extern crate rocket_sync_db_pools;
 use diesel::{insert_into, prelude::*};
 use rocket::fairing::AdHoc;
 use rocket::form::Form;
+use rocket::http::Status;
+use rocket::http::uri::Origin;
 use rocket::response::Redirect;
-use rocket::serde::json::Json;
+use rocket::response::status::Custom;
+use rocket::serde::json::{Json, Value};
 use rocket::{Build, Error, Rocket, State};
 use rocket_dyn_templates::Template;
 use std::num::NonZeroU8;
 use std::sync::atomic::Ordering::Relaxed;
 
+pub mod admin;
+use admin::blocked_domain;
 pub mod connections;
 pub mod functions;
 use functions::{
-    get_epoch, get_instances, is_cached, rating_to_percent, rocket, run_db_migrations, set_cached,
-    strip_url, update_instance_cache,
+    configure_probes, format_duration, get_epoch, is_cached, rating_to_percent, replace_query,
+    rocket, run_db_migrations, set_cached, set_instance_status, strip_url, update_instance_cache,
 };
 pub mod models;
 use models::{
-    AddForm, CSP_RECOMMENDATION, CheckNew, DirectoryDbConn, HtmlTable, Instance, InstancePage,
-    InstancesCache, PrivateBin, ScanNew, StatusPage, TITLE, TablePage,
+    AboutPage, AddForm, CheckNew, CheckStatus, CspComparison, DirectoryConfig, DirectoryDbConn,
+    DirectoryError, FilterOperators, HeaderChecks, HeaderNew, HtmlTable, IndexGrouping, Instance,
+    InstanceEvent, InstanceEventNew, InstanceFilter, InstanceList, InstancePage, InstanceSort,
+    InstanceStatus, InstancesCache, OperatorForm, PageLink, PrivateBin, Ratings, Scan, ScanNew,
+    SortOrder, StatusPage, TITLE, TablePage, Uptime, UptimeResolution,
 };
+pub mod openapi;
+pub mod regions;
+use regions::{Continent, Location};
+pub mod robots;
+pub mod scanners;
+use scanners::TlsDetails;
 pub mod schema;
 use schema::checks::dsl::checks;
+use schema::headers::dsl::headers;
 use schema::scans::dsl::scans;
 pub mod tasks;
-use tasks::{CRON_INTERVAL, check_full, check_up};
+use tasks::{check_full, check_up, scheduler};
+#[cfg(test)]
+mod test_support;
 #[cfg(test)]
 mod tests;
 
 const ADD_TITLE: &str = "Add instance";
 const CHECK_TITLE: &str = "Check instance";
-
-#[get("/")]
-async fn index(db: DirectoryDbConn, cache: &State<InstancesCache>) -> Template {
-    update_instance_cache(db, cache).await;
-
-    let header = [
-        "Address".into(),
-        "Version".into(),
-        "HTTPS".into(),
-        "HTTPS enforced".into(),
-        "recommended CSP".into(),
-        "Observatory Rating".into(),
-        "File upload".into(),
-        "Uptime".into(),
-        "Country".into(),
-    ];
-    let mut tables = vec![];
-    let mut body = vec![];
-    let (mut major, mut minor) = (0, 0);
-    for instance in &*cache.instances.read().unwrap() {
-        // parse the major and minor bits of the version
-        let mmp: Vec<u16> = instance
-            .version
-            .split('.')
-            .filter_map(|s| s.parse::<u16>().ok())
-            .collect();
-        if mmp.is_empty() {
-            continue;
+const OPERATOR_TITLE: &str = "Manage instance";
+const UPTIME_DEFAULT_RANGE: i64 = 30 * 86400; // 30 days
+
+#[get("/?<group>&<sort>&<order>&<filter..>")]
+#[allow(clippy::too_many_arguments, clippy::too_many_lines)]
+async fn index(
+    group: Option<IndexGrouping>,
+    sort: Option<InstanceSort>,
+    order: Option<SortOrder>,
+    filter: InstanceFilter,
+    _operators: FilterOperators,
+    uri: &Origin<'_>,
+    db: DirectoryDbConn,
+    cache: &State<InstancesCache>,
+    config: &State<DirectoryConfig>,
+) -> Template {
+    update_instance_cache(&db, cache, config).await;
+
+    let grouping = group.unwrap_or_default();
+    let current_order = sort.map(|sort| order.unwrap_or_else(|| sort.default_order()));
+    let header: Vec<PageLink> = [
+        ("Address", Some(InstanceSort::Url)),
+        ("Version", Some(InstanceSort::Version)),
+        ("HTTPS", None),
+        ("HTTPS enforced", None),
+        ("recommended CSP", None),
+        ("Observatory Rating", Some(InstanceSort::Rating)),
+        ("File upload", None),
+        ("Discussion", None),
+        ("Password", None),
+        ("Max. expiry", None),
+        ("Uptime", Some(InstanceSort::Uptime)),
+        ("Latency", None),
+        ("Country", None),
+        ("Details", None),
+    ]
+    .into_iter()
+    .map(|(label, column_sort)| {
+        let Some(column_sort) = column_sort else {
+            return PageLink {
+                label: label.into(),
+                ..PageLink::default()
+            };
+        };
+        // the sorted column toggles its order, the others start with their default one
+        let active = sort == Some(column_sort);
+        let (column_order, arrow) = match current_order {
+            Some(order) if active => (
+                order.reverse(),
+                match order {
+                    SortOrder::Asc => " ▲",
+                    SortOrder::Desc => " ▼",
+                },
+            ),
+            _ => (column_sort.default_order(), ""),
+        };
+        PageLink {
+            label: format!("{label}{arrow}"),
+            href: replace_query(
+                uri,
+                &[
+                    ("sort", column_sort.as_str()),
+                    ("order", column_order.as_str()),
+                ],
+            ),
+            active,
         }
-        let (instance_major, instance_minor) = (mmp[0], mmp[1]);
-
-        if major != instance_major || minor != instance_minor {
-            // close table, unless it is the first instance in the list
-            if major != 0 || minor != 0 {
-                tables.push(HtmlTable {
-                    title: format!("Version {major}.{minor}"),
-                    header: header.to_owned(),
-                    body: body.clone(),
-                });
-                // start a new one
-                body.clear();
+    })
+    .collect();
+
+    // split the matching instances into groups, keeping the order of the cache, by version
+    let cached_instances = cache.instances.read().unwrap();
+    let mut groups: Vec<(String, Vec<&Instance>)> = match grouping {
+        IndexGrouping::Version => vec![],
+        IndexGrouping::Region => Continent::ALL
+            .into_iter()
+            .map(Continent::name)
+            .chain(["Unknown region"])
+            .map(|title| (title.into(), vec![]))
+            .collect(),
+    };
+    for instance in cached_instances
+        .iter()
+        .filter(|instance| filter.matches(instance))
+    {
+        let title = match grouping {
+            IndexGrouping::Version => {
+                // parse the major and minor bits of the version
+                let mmp: Vec<u16> = instance
+                    .version
+                    .split('.')
+                    .filter_map(|s| s.parse::<u16>().ok())
+                    .collect();
+                if mmp.len() < 2 {
+                    continue;
+                }
+                format!("Version {}.{}", mmp[0], mmp[1])
             }
-            major = instance_major;
-            minor = instance_minor;
+            IndexGrouping::Region => Continent::from_name(&instance.location.region)
+                .map_or("Unknown region", Continent::name)
+                .into(),
+        };
+        match groups
+            .iter_mut()
+            .find(|(group_title, _)| *group_title == title)
+        {
+            Some((_, members)) => members.push(instance),
+            None => groups.push((title, vec![instance])),
         }
-
-        // format current instance for table display
-        body.push([
-            format!("opacity{}", instance.uptime / 25),
-            instance.url.clone(),
-            instance.version.clone(),
-            Instance::format(instance.https),
-            Instance::format(instance.https_redirect),
-            Instance::format(instance.csp_header),
-            instance.rating_mozilla_observatory.clone(),
-            Instance::format(instance.attachments),
-            format!("{}%", instance.uptime),
-            instance.country_id.clone(),
-        ]);
     }
-    tables.push(HtmlTable {
-        title: format!("Version {major}.{minor}"),
-        header,
-        body,
-    });
-
-    Template::render("list", TablePage::new("Welcome!".into(), tables))
+    let total = cached_instances.len();
+    let shown = groups.iter().map(|(_, members)| members.len()).sum();
+    let tables = groups
+        .into_iter()
+        .filter(|(_, members)| !members.is_empty())
+        .map(|(title, mut members)| {
+            if let (Some(sort), Some(order)) = (sort, current_order) {
+                members.sort_by(|a, b| match order {
+                    SortOrder::Asc => sort.compare(a, b),
+                    SortOrder::Desc => sort.compare(b, a),
+                });
+            }
+            // format the instances for table display
+            let body = members
+                .into_iter()
+                .map(|instance| {
+                    [
+                        format!("opacity{}", instance.uptime / 25),
+                        instance.url.clone(),
+                        instance.version.clone(),
+                        Instance::format(instance.https),
+                        Instance::format(instance.https_redirect),
+                        Instance::format(instance.csp_header),
+                        instance.rating_mozilla_observatory.clone(),
+                        Instance::format(instance.attachments),
+                        Instance::format(instance.features.discussion),
+                        Instance::format(instance.features.password),
+                        Instance::format_expiry(instance.features.max_expiry().unwrap_or_default()),
+                        format!("{}%", instance.uptime),
+                        Instance::format_latency(instance.latency_p50),
+                        instance.country_id.clone(),
+                        format!("/instance/{}", instance.id),
+                    ]
+                })
+                .collect();
+            HtmlTable {
+                title,
+                header: header.clone(),
+                body,
+            }
+        })
+        .collect();
+    drop(cached_instances);
+
+    // the filter is set if the query contains more than the grouping and sorting
+    let view: Vec<(&str, &str)> = [
+        ("group", group.map(IndexGrouping::as_str)),
+        ("sort", sort.map(InstanceSort::as_str)),
+        ("order", order.map(SortOrder::as_str)),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| Some((name, value?)))
+    .collect();
+    let view_query = format!(
+        "?{}",
+        view.iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<String>>()
+            .join("&")
+    );
+
+    let last_full_check = Some(cache.last_full_check.load(Relaxed)).filter(|&time| time > 0);
+    let mut page = TablePage::new("Welcome!".into(), tables, last_full_check);
+    page.groupings = IndexGrouping::ALL
+        .into_iter()
+        .map(|option| PageLink {
+            label: option.name().into(),
+            href: replace_query(uri, &[("group", option.as_str())]),
+            active: option == grouping,
+        })
+        .collect();
+    if replace_query(uri, &view) != view_query {
+        page.filter_reset = view_query;
+    }
+    page.view = view
+        .into_iter()
+        .map(|(name, value)| (name.into(), value.into()))
+        .collect();
+    page.filter = filter;
+    page.shown = shown;
+    page.total = total;
+    Template::render("list", page)
 }
 
 #[get("/about")]
-fn about() -> Template {
+fn about(config: &State<DirectoryConfig>) -> Template {
     Template::render(
         "about",
-        StatusPage::new(
-            format!("About the {TITLE}"),
-            Some(CSP_RECOMMENDATION.into()),
-            Some(env!("CARGO_PKG_VERSION").into()),
-        ),
+        AboutPage::new(format!("About the {TITLE}"), config),
     )
 }
 
@@ -127,18 +265,38 @@ fn add() -> Template {
 }
 
 #[post("/add", data = "<form>")]
-async fn save(db: DirectoryDbConn, form: Form<AddForm>, cache: &State<InstancesCache>) -> Template {
+#[allow(clippy::too_many_lines)]
+async fn save(
+    db: DirectoryDbConn,
+    form: Form<AddForm>,
+    cache: &State<InstancesCache>,
+    config: &State<DirectoryConfig>,
+) -> Template {
     let form = form.into_inner();
     let add_url = form.url.trim();
 
+    // moderators may have blocked the domain, including its subdomains
+    let blocked_url = add_url.to_string();
+    if let Ok(Some(_)) = db.run(move |conn| blocked_domain(conn, &blocked_url)).await {
+        return Template::render(
+            "form",
+            StatusPage::new(
+                ADD_TITLE.into(),
+                Some(DirectoryError::Blocklisted(add_url.into()).to_string()),
+                None,
+            ),
+        );
+    }
+
     // check in negative lookup cache, prevent unnecessary lookups
-    if is_cached(&cache.negative_lookups, add_url) {
+    if is_cached(&cache.negative_lookups, add_url, config.cache_timeout) {
         return Template::render(
             "form",
             StatusPage::new(
                 ADD_TITLE.into(),
                 Some(format!(
-                    "Error adding URL {add_url}, due to a failed scan within the last 5 minutes."
+                    "Error adding URL {add_url}, due to a failed scan within the last {}.",
+                    format_duration(config.cache_timeout)
                 )),
                 None,
             ),
@@ -150,7 +308,42 @@ async fn save(db: DirectoryDbConn, form: Form<AddForm>, cache: &State<InstancesC
     let (do_cache_flush, page) = match privatebin_result {
         Ok(privatebin) => {
             db.run(move |conn| {
-                use schema::instances::dsl::{id, instances, url};
+                use schema::instances::dsl::{failures, id, instances, status, url};
+
+                // instances taken off the list get re-activated, instead of added again
+                if let Ok((instance_id, instance_status)) = instances
+                    .select((id, status))
+                    .filter(url.eq(&privatebin.instance.url))
+                    .first::<(i32, String)>(conn)
+                    && let Some(old_status) = InstanceStatus::from_name(&instance_status)
+                    && !old_status.is_listed()
+                {
+                    let add_url = privatebin.instance.url;
+                    return match conn.transaction(|conn| {
+                        set_instance_status(conn, instance_id, old_status, InstanceStatus::Active)?;
+                        diesel::update(instances.filter(id.eq(instance_id)))
+                            .set(failures.eq(0))
+                            .execute(conn)
+                            .map(|_| ())
+                    }) {
+                        Ok(()) => (
+                            true,
+                            StatusPage::new(
+                                ADD_TITLE.into(),
+                                None,
+                                Some(format!("Successfully re-activated URL: {add_url}")),
+                            ),
+                        ),
+                        Err(e) => (
+                            false,
+                            StatusPage::new(
+                                ADD_TITLE.into(),
+                                Some(format!("Error re-activating URL {add_url}, due to: {e:?}")),
+                                None,
+                            ),
+                        ),
+                    };
+                }
                 match insert_into(instances)
                     .values(&privatebin.instance)
                     .execute(conn)
@@ -167,13 +360,35 @@ async fn save(db: DirectoryDbConn, form: Form<AddForm>, cache: &State<InstancesC
                             .values(CheckNew {
                                 up: true,
                                 instance_id,
+                                latency: 0,
+                                status: 200,
                             })
                             .execute(conn)
                             .expect("inserting first check on a newly created instance");
+                        let instance_scans: Vec<ScanNew> = privatebin
+                            .scans
+                            .into_iter()
+                            .map(|scan| ScanNew {
+                                instance_id,
+                                ..scan
+                            })
+                            .collect();
                         insert_into(scans)
-                            .values(ScanNew::new("mozilla_observatory", "-", instance_id))
+                            .values(&instance_scans)
+                            .execute(conn)
+                            .expect("inserting first scans on a newly created instance");
+                        let instance_headers: Vec<HeaderNew> = privatebin
+                            .headers
+                            .into_iter()
+                            .map(|header| HeaderNew {
+                                instance_id,
+                                ..header
+                            })
+                            .collect();
+                        insert_into(headers)
+                            .values(&instance_headers)
                             .execute(conn)
-                            .expect("inserting first scan on a newly created instance");
+                            .expect("inserting header checks on a newly created instance");
 
                         let add_url = privatebin.instance.url;
                         (
@@ -201,9 +416,12 @@ async fn save(db: DirectoryDbConn, form: Form<AddForm>, cache: &State<InstancesC
             .await
         }
         Err(e) => {
-            // don't query this site again, for another 5 minutes
+            // don't query this site again, until the cache timeout passed
             set_cached(&cache.negative_lookups, add_url);
-            (false, StatusPage::new(ADD_TITLE.into(), Some(e), None))
+            (
+                false,
+                StatusPage::new(ADD_TITLE.into(), Some(e.to_string()), None),
+            )
         }
     };
     if do_cache_flush {
@@ -212,6 +430,107 @@ async fn save(db: DirectoryDbConn, form: Form<AddForm>, cache: &State<InstancesC
     Template::render("form", page)
 }
 
+#[get("/operator")]
+fn operator() -> Template {
+    Template::render(
+        "operator",
+        StatusPage::new(OPERATOR_TITLE.into(), None, None),
+    )
+}
+
+#[post("/operator", data = "<form>")]
+async fn operator_save(
+    db: DirectoryDbConn,
+    form: Form<OperatorForm>,
+    cache: &State<InstancesCache>,
+) -> Template {
+    let form = form.into_inner();
+    let page = match update_operator_settings(db, form).await {
+        Ok(message) => {
+            cache.timeout.store(0, Relaxed);
+            StatusPage::new(OPERATOR_TITLE.into(), None, Some(message))
+        }
+        Err(e) => StatusPage::new(OPERATOR_TITLE.into(), Some(e.to_string()), None),
+    };
+    Template::render("operator", page)
+}
+
+// store the settings of an instance, once the ownership got verified
+async fn update_operator_settings(
+    db: DirectoryDbConn,
+    form: OperatorForm,
+) -> Result<String, DirectoryError> {
+    use schema::instances::dsl::{
+        contact, delisted, description, display_name, id, instances, url, verified,
+    };
+
+    form.validate()?;
+    let operator_url = strip_url(form.url.trim().into());
+    let lookup_url = operator_url.clone();
+    let instance = db
+        .run(move |conn| {
+            instances
+                .select((id, display_name, description, contact, delisted))
+                .filter(url.eq(lookup_url))
+                .first::<(i32, String, String, String, bool)>(conn)
+        })
+        .await
+        .map_err(|_| DirectoryError::UnknownInstance(operator_url.clone()))?;
+    PrivateBin::verify_ownership(&operator_url, &form.secret).await?;
+
+    let (instance_id, old_display_name, old_description, old_contact, old_delisted) = instance;
+    let new_values = (
+        form.display_name.trim().to_string(),
+        form.description.trim().to_string(),
+        form.contact.trim().to_string(),
+        form.delisted,
+    );
+    let events: Vec<InstanceEventNew> = [
+        ("display_name", old_display_name, new_values.0.clone()),
+        ("description", old_description, new_values.1.clone()),
+        ("contact", old_contact, new_values.2.clone()),
+        (
+            "delisted",
+            format!("{old_delisted:?}"),
+            format!("{:?}", new_values.3),
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, old_value, new_value)| old_value != new_value)
+    .map(|(field, old_value, new_value)| InstanceEventNew {
+        field: field.into(),
+        old_value,
+        new_value,
+        instance_id,
+    })
+    .collect();
+    db.run(move |conn| {
+        use schema::instance_events::dsl::instance_events;
+        conn.transaction(|conn| {
+            let count = diesel::update(instances.filter(id.eq(instance_id)))
+                .set((
+                    verified.eq(true),
+                    display_name.eq(new_values.0),
+                    description.eq(new_values.1),
+                    contact.eq(new_values.2),
+                    delisted.eq(new_values.3),
+                ))
+                .execute(conn)?;
+            if count == 0 {
+                return Err(diesel::result::Error::NotFound);
+            }
+            insert_into(instance_events).values(&events).execute(conn)
+        })
+    })
+    .await
+    .map_err(|_| DirectoryError::UnknownInstance(operator_url.clone()))?;
+    Ok(if form.delisted {
+        format!("Successfully verified URL {operator_url}, it will no longer be listed.")
+    } else {
+        format!("Successfully verified URL {operator_url} and updated its settings.")
+    })
+}
+
 #[get("/check")]
 fn check() -> Template {
     Template::render("form", StatusPage::new(CHECK_TITLE.into(), None, None))
@@ -222,209 +541,392 @@ async fn report(
     db: DirectoryDbConn,
     form: Form<AddForm>,
     cache: &State<InstancesCache>,
+    config: &State<DirectoryConfig>,
 ) -> Template {
     let form = form.into_inner();
     let form_url = form.url.trim().to_string();
-    let check_url = strip_url(form_url.clone());
-    let lookup_url = check_url.clone();
-    let check_success_title = format!("Results of checking {check_url}");
+    match check_url(&db, &form_url, cache, config).await {
+        Ok((instance, csp)) => {
+            // instances that aren't listed yet have no history
+            let instance_id = instance.id;
+            let events = if instance_id == 0 {
+                vec![]
+            } else {
+                db.run(move |conn| load_events(conn, instance_id, None))
+                    .await
+            };
+            let check_success_title = format!("Results of checking {}", strip_url(form_url));
+            let mut page = InstancePage::new(check_success_title, Some(instance), None);
+            page.csp = csp;
+            page.events = events;
+            Template::render("check", page)
+        }
+        Err(e) => {
+            let error = match e {
+                DirectoryError::RecentlyFailed(seconds) => format!(
+                    "Error scanning URL {form_url}, due to a failed scan within the last {}.",
+                    format_duration(seconds)
+                ),
+                e => format!("Error scanning URL {form_url}, due to: {e}"),
+            };
+            Template::render(
+                "form",
+                StatusPage::new(CHECK_TITLE.into(), Some(error), None),
+            )
+        }
+    }
+}
+
+#[get("/api/check?<url>", format = "json")]
+async fn api_check(
+    url: &str,
+    db: DirectoryDbConn,
+    cache: &State<InstancesCache>,
+    config: &State<DirectoryConfig>,
+) -> Result<Json<Instance>, Custom<Json<DirectoryError>>> {
+    match check_url(&db, url.trim(), cache, config).await {
+        Ok((instance, _)) => Ok(Json(instance)),
+        Err(e) => Err(Custom(e.status(), Json(e))),
+    }
+}
+
+// look up the instance in the directory or scan it, if it is unknown, along with the policy
+// explaining a failed CSP check
+async fn check_url(
+    db: &DirectoryDbConn,
+    form_url: &str,
+    cache: &State<InstancesCache>,
+    config: &State<DirectoryConfig>,
+) -> Result<(Instance, Option<CspComparison>), DirectoryError> {
+    let check_url = strip_url(form_url.into());
 
     // check in negative lookup cache, prevent unnecessary lookups
-    if is_cached(&cache.negative_lookups, &check_url) {
-        return Template::render(
-            "form",
-            StatusPage::new(
-                CHECK_TITLE.into(),
-                Some(format!(
-                    "Error scanning URL {form_url}, due to a failed scan within the last 5 minutes."
-                )),
-                None,
-            ),
-        );
+    if is_cached(&cache.negative_lookups, &check_url, config.cache_timeout) {
+        return Err(DirectoryError::RecentlyFailed(config.cache_timeout));
     }
 
     // check in database
-    let instance_in_db = db
+    update_instance_cache(db, cache, config).await;
+    let instance_in_db = cache
+        .instances
+        .read()
+        .unwrap()
+        .iter()
+        .find(|instance| instance.url == check_url)
+        .cloned();
+    if let Some(instance) = instance_in_db {
+        // explain why the check failed, using the policy stored by the last full check
+        let csp = (!instance.csp_header).then(|| {
+            CspComparison::new(
+                &instance.csp_policy,
+                &instance.version,
+                instance.features.template.starts_with("bootstrap"),
+            )
+        });
+        return Ok((instance, csp));
+    }
+
+    // scan unknown instance
+    match PrivateBin::new(form_url.into()).await {
+        Ok(privatebin) => {
+            let mut instance = Instance {
+                id: 0,
+                url: privatebin.instance.url,
+                version: privatebin.instance.version,
+                https: privatebin.instance.https,
+                https_redirect: privatebin.instance.https_redirect,
+                location: Location::from(privatebin.instance.country_id.as_str()),
+                country_id: privatebin.instance.country_id,
+                attachments: privatebin.instance.attachments,
+                csp_header: privatebin.instance.csp_header,
+                csp_policy: privatebin.instance.csp_policy,
+                features: privatebin.instance.features,
+                uptime: 0,
+                latency_p50: 0,
+                latency_p95: 0,
+                rating_mozilla_observatory: String::new(),
+                ratings: Ratings::from(privatebin.scans.as_slice()),
+                cert_days: TlsDetails::cert_days(&privatebin.scans),
+                headers: HeaderChecks::from(privatebin.headers.as_slice()),
+                status: InstanceStatus::Active.as_str().into(),
+                failures: 0,
+                verified: false,
+                display_name: String::new(),
+                description: String::new(),
+                contact: String::new(),
+            };
+            instance
+                .ratings
+                .get("mozilla_observatory")
+                .clone_into(&mut instance.rating_mozilla_observatory);
+            Ok((instance, Some(privatebin.csp)))
+        }
+        Err(e) => {
+            // don't query this site again, until the cache timeout passed
+            set_cached(&cache.negative_lookups, &check_url);
+            Err(e)
+        }
+    }
+}
+
+#[get("/instance/<instance_id>")]
+async fn instance(
+    instance_id: i32,
+    db: DirectoryDbConn,
+    cache: &State<InstancesCache>,
+    config: &State<DirectoryConfig>,
+) -> Option<Template> {
+    let (instance_checks, instance_scans, events) = db
         .run(move |conn| {
             use diesel::dsl::sql;
-            use diesel::sql_types::{Integer, Text};
-            use schema::instances::dsl::{
-                attachments, country_id, csp_header, https, https_redirect, id, instances, url,
-                version,
+            use diesel::sql_types::Text;
+            use schema::checks::dsl::{
+                instance_id as check_instance_id, latency, status, up, updated,
             };
-            instances
-                .select((
-                    id,
-                    url,
-                    version,
-                    https,
-                    https_redirect,
-                    country_id,
-                    attachments,
-                    csp_header,
-                    sql::<Integer>("(100 * SUM(checks.up) / COUNT(checks.up))"),
-                    sql::<Text>("scans.rating"),
-                ))
-                .inner_join(checks)
-                .inner_join(scans)
-                .filter(url.eq(lookup_url))
-                .first(conn)
+            use schema::scans::dsl::instance_id as scan_instance_id;
+            (
+                checks
+                    .select((sql::<Text>("checks.updated"), up, latency, status))
+                    .filter(check_instance_id.eq(instance_id))
+                    .order(updated.asc())
+                    .load::<CheckStatus>(conn)
+                    .unwrap_or_default(),
+                scans
+                    .filter(scan_instance_id.eq(instance_id))
+                    .load::<Scan>(conn)
+                    .unwrap_or_default(),
+                load_events(conn, instance_id, None),
+            )
         })
         .await;
-    let page = match instance_in_db {
-        Ok(instance) => InstancePage::new(check_success_title, Some(instance), None),
-        Err(_) => match PrivateBin::new(form_url.clone()).await {
-            // scan unknown instance
-            Ok(privatebin) => {
-                let instance = Instance {
-                    id: 0,
-                    url: privatebin.instance.url,
-                    version: privatebin.instance.version,
-                    https: privatebin.instance.https,
-                    https_redirect: privatebin.instance.https_redirect,
-                    country_id: privatebin.instance.country_id,
-                    attachments: privatebin.instance.attachments,
-                    csp_header: privatebin.instance.csp_header,
-                    uptime: 0,
-                    rating_mozilla_observatory: privatebin
-                        .scans
-                        .last()
-                        .unwrap_or(&ScanNew::default())
-                        .rating
-                        .clone(),
-                };
-                InstancePage::new(check_success_title, Some(instance), None)
-            }
-            Err(e) => {
-                // don't query this site again, for another 5 minutes
-                set_cached(&cache.negative_lookups, &check_url);
-                InstancePage::new(
-                    CHECK_TITLE.into(),
-                    None,
-                    Some(format!("Error scanning URL {form_url}, due to: {e:?}")),
-                )
-            }
-        },
-    };
-    if page.error.is_empty() {
-        return Template::render("check", page);
-    }
-    Template::render("form", StatusPage::new(page.topic, Some(page.error), None))
+    update_instance_cache(&db, cache, config).await;
+
+    let instance = cache
+        .instances
+        .read()
+        .unwrap()
+        .iter()
+        .find(|instance| instance.id == instance_id)?
+        .to_owned();
+    let mut page = InstancePage::new(format!("Details of {}", instance.url), Some(instance), None);
+    page.checks = instance_checks;
+    page.scans = instance_scans;
+    page.events = events;
+    Some(Template::render("check", page))
 }
 
-#[allow(clippy::too_many_arguments)]
-#[get(
-    "/api?<top>&<attachments>&<country>&<csp_header>&<https>&<https_redirect>&<version>&<min_uptime>&<min_rating>",
-    format = "json"
-)]
+#[get("/api?<top>&<filter..>", format = "json")]
 async fn api(
     top: Option<NonZeroU8>,
-    attachments: Option<bool>,
-    country: Option<String>,
-    csp_header: Option<bool>,
-    https: Option<bool>,
-    https_redirect: Option<bool>,
-    version: Option<String>,
-    min_uptime: Option<u8>,
-    min_rating: Option<String>,
+    filter: InstanceFilter,
+    _operators: FilterOperators,
     db: DirectoryDbConn,
     cache: &State<InstancesCache>,
+    config: &State<DirectoryConfig>,
 ) -> Json<Vec<Instance>> {
     use rand::seq::SliceRandom;
-    let mut instance_list: Vec<Instance> = vec![];
-    update_instance_cache(db, cache).await;
+    update_instance_cache(&db, cache, config).await;
 
     // unwrap & validate arguments
-    let mut top: u8 = top.unwrap_or_else(|| NonZeroU8::new(10).unwrap()).into();
-    if top > 100 {
-        top = 100;
-    }
-
-    let is_attachments_set = attachments.is_some();
-    let attachments = attachments.unwrap_or(false);
-
-    let is_country_set = country.is_some();
-    let country = country.unwrap_or_default();
+    let top: u8 = top.unwrap_or_else(|| NonZeroU8::new(10).unwrap()).into();
+    let top = top.min(config.api_max_top);
 
-    let is_csp_header_set = csp_header.is_some();
-    let csp_header = csp_header.unwrap_or(false);
+    // prepare list according to arguments
+    let mut instance_list: Vec<Instance> = cache
+        .instances
+        .read()
+        .unwrap()
+        .iter()
+        .filter(|instance| filter.matches(instance))
+        .take(top.into())
+        .cloned()
+        .collect();
+    let mut rng = rand::rng();
+    instance_list.shuffle(&mut rng);
+    Json(instance_list)
+}
 
-    let is_https_set = https.is_some();
-    let https = https.unwrap_or(false);
+#[get(
+    "/api/v2/instances?<sort>&<order>&<offset>&<limit>&<filter..>",
+    format = "json"
+)]
+#[allow(clippy::too_many_arguments)]
+async fn api_v2_instances(
+    sort: Option<InstanceSort>,
+    order: Option<SortOrder>,
+    offset: Option<usize>,
+    limit: Option<NonZeroU8>,
+    filter: InstanceFilter,
+    _operators: FilterOperators,
+    uri: &Origin<'_>,
+    db: DirectoryDbConn,
+    cache: &State<InstancesCache>,
+    config: &State<DirectoryConfig>,
+) -> Json<InstanceList> {
+    update_instance_cache(&db, cache, config).await;
 
-    let is_https_redirect_set = https_redirect.is_some();
-    let https_redirect = https_redirect.unwrap_or(false);
+    // unwrap & validate arguments
+    let sort = sort.unwrap_or(InstanceSort::Url);
+    let order = order.unwrap_or_else(|| sort.default_order());
+    let offset = offset.unwrap_or(0);
+    let limit = limit.map_or(config.api_max_top, |limit| {
+        u8::from(limit).min(config.api_max_top)
+    });
 
-    let is_version_set = version.is_some();
-    let version = version.unwrap_or_default();
+    let cached_instances = cache.instances.read().unwrap();
+    let mut matching: Vec<&Instance> = cached_instances
+        .iter()
+        .filter(|instance| filter.matches(instance))
+        .collect();
+    matching.sort_by(|a, b| match order {
+        SortOrder::Asc => sort.compare(a, b),
+        SortOrder::Desc => sort.compare(b, a),
+    });
+    let total = matching.len();
+    let instances: Vec<Instance> = matching
+        .into_iter()
+        .skip(offset)
+        .take(limit.into())
+        .cloned()
+        .collect();
+    drop(cached_instances);
+
+    // link the following page by repeating the query with the offset moved forward
+    let next_offset = offset.saturating_add(limit.into());
+    let next =
+        (next_offset < total).then(|| replace_query(uri, &[("offset", &next_offset.to_string())]));
+    Json(InstanceList {
+        total,
+        offset,
+        limit,
+        sort,
+        order,
+        filter,
+        next,
+        instances,
+    })
+}
 
-    let mut min_uptime: i32 = min_uptime.unwrap_or(0).into();
-    if min_uptime > 100 {
-        min_uptime = 100;
-    }
+#[get(
+    "/api/instance/<instance>/uptime?<from>&<to>&<resolution>",
+    format = "json"
+)]
+async fn api_uptime(
+    instance: i32,
+    from: Option<i64>,
+    to: Option<i64>,
+    resolution: Option<UptimeResolution>,
+    db: DirectoryDbConn,
+) -> Result<Json<Vec<Uptime>>, Status> {
+    // unwrap & validate arguments
+    let to = to.unwrap_or_else(|| i64::try_from(get_epoch()).unwrap_or(i64::MAX));
+    let from = from.unwrap_or(to - UPTIME_DEFAULT_RANGE);
+    let resolution = resolution.unwrap_or(UptimeResolution::Day);
+
+    db.run(move |conn| {
+        use diesel::sql_types::{BigInt, Integer, Text};
+        use schema::instances::dsl::{id, instances};
+        // unknown instances aren't found, while database errors are
+        instances
+            .select(id)
+            .filter(id.eq(instance))
+            .first::<i32>(conn)
+            .optional()
+            .map_err(|_| Status::InternalServerError)?
+            .ok_or(Status::NotFound)?;
+        diesel::dsl::sql_query(
+            "SELECT CAST(strftime('%s', period) AS INTEGER) AS period, checks, \
+                (100 * up / checks) AS uptime \
+                FROM uptimes \
+                WHERE instance_id = ? AND resolution = ? \
+                AND period >= datetime(?, 'unixepoch') AND period < datetime(?, 'unixepoch') \
+                ORDER BY period ASC",
+        )
+        .bind::<Integer, _>(instance)
+        .bind::<Text, _>(resolution.as_str())
+        .bind::<BigInt, _>(from)
+        .bind::<BigInt, _>(to)
+        .load::<Uptime>(conn)
+        .map_err(|_| Status::InternalServerError)
+    })
+    .await
+    .map(Json)
+}
 
-    let is_min_rating_set = min_rating.is_some();
-    let min_rating = rating_to_percent(&min_rating.unwrap_or_else(|| "F".into()));
+#[get("/api/instance/<instance>/events?<field>", format = "json")]
+async fn api_events(
+    instance: i32,
+    field: Option<String>,
+    db: DirectoryDbConn,
+) -> Option<Json<Vec<InstanceEvent>>> {
+    db.run(move |conn| {
+        use schema::instances::dsl::{id, instances};
+        instances
+            .select(id)
+            .filter(id.eq(instance))
+            .first::<i32>(conn)
+            .ok()?;
+        Some(load_events(conn, instance, field))
+    })
+    .await
+    .map(Json)
+}
 
-    // prepare list according to arguments
-    for instance in &*cache.instances.read().unwrap() {
-        if (is_csp_header_set && instance.csp_header != csp_header)
-            || (is_https_set && instance.https != https)
-            || (is_https_redirect_set && instance.https_redirect != https_redirect)
-            || (is_attachments_set && instance.attachments != attachments)
-            || (instance.uptime < min_uptime)
-            || (is_version_set && !instance.version.starts_with(&version))
-            || (is_country_set && instance.country_id != country)
-            || (is_min_rating_set
-                && rating_to_percent(&instance.rating_mozilla_observatory) < min_rating)
-        {
-            continue;
-        }
+#[get("/api/openapi.json")]
+fn api_openapi(config: &State<DirectoryConfig>) -> Json<Value> {
+    Json(openapi::document(config))
+}
 
-        instance_list.push(instance.to_owned());
-        top -= 1;
-        if top == 0 {
-            break;
-        }
+// changes of an instance, oldest first, optionally limited to a single field
+fn load_events(
+    conn: &mut SqliteConnection,
+    instance: i32,
+    event_field: Option<String>,
+) -> Vec<InstanceEvent> {
+    use diesel::dsl::sql;
+    use diesel::sql_types::BigInt;
+    use schema::instance_events::dsl::{
+        field, id, instance_events, instance_id, new_value, old_value,
+    };
+    let mut query = instance_events
+        .select((
+            sql::<BigInt>("CAST(strftime('%s', instance_events.updated) AS INTEGER)"),
+            field,
+            old_value,
+            new_value,
+        ))
+        .filter(instance_id.eq(instance))
+        .order(id.asc())
+        .into_boxed();
+    if let Some(event_field) = event_field {
+        query = query.filter(field.eq(event_field));
     }
-    let mut rng = rand::rng();
-    instance_list.shuffle(&mut rng);
-    Json(instance_list)
+    query.load::<InstanceEvent>(conn).unwrap_or_default()
 }
 
-#[get("/forward-me?<attachments>&<country>&<version>")]
+#[get("/forward-me?<filter..>")]
 async fn forward_me(
-    attachments: Option<bool>,
-    country: Option<String>,
-    version: Option<String>,
+    filter: InstanceFilter,
+    _operators: FilterOperators,
     db: DirectoryDbConn,
     cache: &State<InstancesCache>,
+    config: &State<DirectoryConfig>,
 ) -> Redirect {
     use rand::seq::SliceRandom;
     let mut instance_list: Vec<Instance> = vec![];
-    update_instance_cache(db, cache).await;
-
-    // unwrap & validate arguments
-    let is_attachments_set = attachments.is_some();
-    let attachments = attachments.unwrap_or(false);
-
-    let is_country_set = country.is_some();
-    let country = country.unwrap_or_default();
-
-    let is_version_set = version.is_some();
-    let version = version.unwrap_or_default();
+    update_instance_cache(&db, cache, config).await;
+    let is_version_set = filter.constrains_version();
 
-    // prepare list according to arguments and hardcoded filter criteria
+    // prepare list according to arguments and configured filter criteria
+    let forward_min_rating = rating_to_percent(&config.forward_min_rating);
     let mut instance_version = String::new();
     for instance in &*cache.instances.read().unwrap() {
         if !instance.https
             || !instance.https_redirect
             || (!instance.csp_header && !is_version_set) // don't enforce CSP for older versions, most wont have it
-            || instance.uptime < 100
-            || rating_to_percent(&instance.rating_mozilla_observatory) < 90
-            || (is_version_set && !instance.version.starts_with(&version))
-            || (is_attachments_set && instance.attachments != attachments)
-            || (is_country_set && instance.country_id != country)
+            || instance.uptime < config.forward_min_uptime
+            || rating_to_percent(&instance.rating_mozilla_observatory) < forward_min_rating
+            || !filter.matches(instance)
         {
             continue;
         }
@@ -459,16 +961,27 @@ fn favicon() -> Redirect {
 #[rocket::main]
 async fn main() -> Result<(), Error> {
     let rocket = rocket();
+    if let Some(config) = rocket.state::<DirectoryConfig>() {
+        configure_probes(config);
+    }
     if let Ok(cron_env) = std::env::var("CRON") {
+        let rocket = rocket.ignite().await?;
+        let db = DirectoryDbConn::get_one(&rocket)
+            .await
+            .expect("database connection");
+        let config = rocket
+            .state::<DirectoryConfig>()
+            .expect("directory configuration");
         if cron_env == "FULL" {
-            check_full(rocket).await;
+            check_full(&db, config).await;
         } else {
-            check_up(rocket).await;
+            check_up(&db, config).await;
         }
         return Ok(());
     }
     rocket
         .attach(AdHoc::on_ignite("Diesel Migrations", run_db_migrations))
+        .attach(scheduler())
         .launch()
         .await
         .map(|_| ())
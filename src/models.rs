@@ -1,26 +1,49 @@
-use super::connections::{CLOSE, request, request_get, request_head, request_post};
-use super::functions::{rating_to_percent, strip_url};
+use super::connections::{
+    CLOSE, HOST_DELAY_MS, KEEPALIVE, REQUESTS_PER_SECOND, TIMEOUT_SECONDS, request, request_get,
+    request_head, timed_request,
+};
+use super::functions::{
+    CACHE_TIMEOUT, RATINGS, expiry_to_seconds, format_duration, rating_to_percent, strip_url,
+};
+use super::regions::{Location, UNKNOWN_COUNTRY, in_region};
+use super::robots::{self, RobotsTxt};
+use super::scanners::SCANNERS;
 use super::schema::checks;
+use super::schema::headers;
+use super::schema::instance_events;
 use super::schema::instances;
 use super::schema::scans;
+use super::tasks::{
+    CHECKS_TO_STORE, CRON_INTERVAL, DEGRADED_AFTER, FULL_CHECK_INTERVAL, MAX_CONCURRENT_CHECKS,
+    MAX_FAILURES, SCHEDULER_JITTER, SUSPENSION_DAYS, TOMBSTONE_DAYS, UPTIMES_TO_STORE,
+};
 use diesel::SqliteConnection;
+use diesel::deserialize::QueryableByName; // required by #[diesel(embed)]
+use futures::future::join_all;
 use http_body_util::BodyExt; // BodyExt provides the Iterator trait
-use hyper::body::{Body, Buf, Bytes}; // Body provides the size_hint() trait, Buf provides the reader() trait
-use hyper::header::{CONTENT_SECURITY_POLICY, LOCATION};
+use hyper::body::{Buf, Bytes}; // Buf provides the reader() trait
+use hyper::header::{
+    CONTENT_SECURITY_POLICY, HeaderMap, LOCATION, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY,
+    X_CONTENT_TYPE_OPTIONS,
+};
 use hyper::{Method, StatusCode};
 use maxminddb::geoip2::Country;
-use rand::prelude::*;
 use regex::Regex;
+use rocket::figment::Figment;
+use rocket::form::{FromFormField, ValueField};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
 use rocket::serde::{Deserialize, Serialize, json};
-use rocket::warn;
-use std::collections::HashMap;
+use rocket_sync_db_pools::ConnectionPool;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::env::var;
+use std::fmt::Write;
 use std::net::{IpAddr, ToSocketAddrs}; // ToSocketAddrs provides the to_socket_addrs() trait
 use std::str::from_utf8;
 use std::sync::OnceLock;
 use std::sync::RwLock;
-use std::sync::atomic::AtomicU64;
-use tokio::time::{Duration, sleep};
+use std::sync::atomic::{AtomicI64, AtomicU64};
+use std::time::Duration;
 use url::Url;
 
 pub const CSP_RECOMMENDATION: &str = "default-src 'none'; base-uri 'self'; \
@@ -29,6 +52,8 @@ pub const CSP_RECOMMENDATION: &str = "default-src 'none'; base-uri 'self'; \
     frame-ancestors 'none'; frame-src blob:; img-src 'self' data: blob:; \
     media-src blob:; object-src blob:; sandbox allow-same-origin allow-scripts \
     allow-forms allow-modals allow-downloads";
+// six months, as recommended by the Mozilla web security guidelines
+const HSTS_MINIMUM_MAX_AGE: u64 = 15_768_000;
 static CSP_MAP: &[(&str, &str)] = &[
     ("2.", CSP_RECOMMENDATION), // removed allow-popups (and the page template that used it)
     (
@@ -104,10 +129,14 @@ static CSP_MAP: &[(&str, &str)] = &[
     allow-downloads",
     ),
 ];
-const OBSERVATORY_API: &str = "https://observatory-api.mdn.mozilla.net/api/v2/scan?host=";
-const OBSERVATORY_MAX_CONTENT_LENGTH: u64 = 10240;
 const MAX_LINE_COUNT: u16 = 1024;
+const MIN_ADMIN_TOKEN_LENGTH: usize = 16;
+const MAX_OWNERSHIP_SIZE: usize = 4096;
+pub const OWNERSHIP_PATH: &str = "/.well-known/privatebin-directory";
+const MAX_ROBOTS_REDIRECTS: u8 = 5; // redirects to follow, as recommended by RFC 9309
 pub const TITLE: &str = "Instance Directory";
+static DATA_ATTRIBUTE_EXP: OnceLock<Regex> = OnceLock::new();
+static OPTION_EXP: OnceLock<Regex> = OnceLock::new();
 static TEMPLATE_EXP: OnceLock<Regex> = OnceLock::new();
 static VERSION_EXP: OnceLock<Regex> = OnceLock::new();
 
@@ -117,6 +146,17 @@ pub struct Check {
     pub updated: u64,
     pub up: bool,
     pub instance_id: i32,
+    pub latency: i32,
+    pub status: i32,
+}
+
+#[derive(Queryable, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CheckStatus {
+    pub updated: String,
+    pub up: bool,
+    pub latency: i32,
+    pub status: i32,
 }
 
 #[derive(Insertable)]
@@ -124,11 +164,231 @@ pub struct Check {
 pub struct CheckNew {
     pub up: bool,
     pub instance_id: i32,
+    pub latency: i32,
+    pub status: i32,
+}
+
+#[derive(QueryableByName, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Uptime {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub period: i64,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub checks: i32,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub uptime: i32,
+}
+
+#[derive(Clone, Copy, FromFormField)]
+pub enum UptimeResolution {
+    Hour,
+    Day,
+}
+
+impl UptimeResolution {
+    pub const ALL: [UptimeResolution; 2] = [UptimeResolution::Hour, UptimeResolution::Day];
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UptimeResolution::Hour => "hour",
+            UptimeResolution::Day => "day",
+        }
+    }
+
+    // SQLite strftime format, truncating a timestamp to the start of its period
+    #[must_use]
+    pub fn period_format(self) -> &'static str {
+        match self {
+            UptimeResolution::Hour => "%Y-%m-%d %H:00:00",
+            UptimeResolution::Day => "%Y-%m-%d 00:00:00",
+        }
+    }
+}
+
+// lifecycle of an instance, driven by consecutive failures, removed instances are kept as
+// tombstones for a while, so they can get re-activated if they come back
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InstanceStatus {
+    Active,
+    Degraded,
+    Suspended,
+    Removed,
+}
+
+impl InstanceStatus {
+    pub const ALL: [InstanceStatus; 4] = [
+        InstanceStatus::Active,
+        InstanceStatus::Degraded,
+        InstanceStatus::Suspended,
+        InstanceStatus::Removed,
+    ];
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            InstanceStatus::Active => "active",
+            InstanceStatus::Degraded => "degraded",
+            InstanceStatus::Suspended => "suspended",
+            InstanceStatus::Removed => "removed",
+        }
+    }
+
+    // none for unknown values, i.e. of a corrupted row, so they don't get listed as active
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<InstanceStatus> {
+        InstanceStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str() == name)
+    }
+
+    // listed in the directory, the API and considered for forwarding
+    #[must_use]
+    pub fn is_listed(self) -> bool {
+        matches!(self, InstanceStatus::Active | InstanceStatus::Degraded)
+    }
+
+    // status following an uptime check, given the consecutive failures so far, suspended and
+    // removed instances only get re-activated by a successful full check
+    #[must_use]
+    pub fn after_check(self, failures: u64, config: &DirectoryConfig) -> InstanceStatus {
+        match self {
+            InstanceStatus::Active | InstanceStatus::Degraded
+                if failures >= config.max_failures =>
+            {
+                InstanceStatus::Suspended
+            }
+            InstanceStatus::Active if failures >= config.degraded_after => InstanceStatus::Degraded,
+            InstanceStatus::Degraded if failures == 0 => InstanceStatus::Active,
+            status => status,
+        }
+    }
 }
 
+// tuning of the directory, read from the directory section of the Rocket configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct DirectoryConfig {
+    pub cron_interval: u64,
+    pub checks_to_store: u64,
+    pub max_failures: u64,
+    pub cache_timeout: u64,
+    pub timeout_seconds: u64,
+    pub observatory_api: Option<String>,
+    pub api_max_top: u8,
+    pub forward_min_uptime: i32,
+    pub forward_min_rating: String,
+    pub scheduler: bool,
+    pub full_check_interval: u64,
+    pub scheduler_jitter: u64,
+    pub max_concurrent_checks: usize,
+    pub host_delay_ms: u64,
+    pub requests_per_second: u32,
+    pub degraded_after: u64,
+    pub suspension_days: u64,
+    pub tombstone_days: u64,
+    pub uptimes_to_store: u64,
+    pub admin_token: Option<String>,
+}
+
+impl Default for DirectoryConfig {
+    fn default() -> Self {
+        Self {
+            cron_interval: CRON_INTERVAL,
+            checks_to_store: CHECKS_TO_STORE,
+            max_failures: MAX_FAILURES,
+            cache_timeout: CACHE_TIMEOUT,
+            timeout_seconds: TIMEOUT_SECONDS,
+            observatory_api: None,
+            api_max_top: 100,
+            forward_min_uptime: 100,
+            forward_min_rating: "A-".into(),
+            scheduler: false,
+            full_check_interval: FULL_CHECK_INTERVAL,
+            scheduler_jitter: SCHEDULER_JITTER,
+            max_concurrent_checks: MAX_CONCURRENT_CHECKS,
+            host_delay_ms: HOST_DELAY_MS,
+            requests_per_second: REQUESTS_PER_SECOND,
+            degraded_after: DEGRADED_AFTER,
+            suspension_days: SUSPENSION_DAYS,
+            tombstone_days: TOMBSTONE_DAYS,
+            uptimes_to_store: UPTIMES_TO_STORE,
+            admin_token: None,
+        }
+    }
+}
+
+impl DirectoryConfig {
+    /// # Errors
+    ///
+    /// Will return `Err` if the directory section contains invalid or out of range values.
+    pub fn from_figment(figment: &Figment) -> Result<DirectoryConfig, String> {
+        if figment.find_value("directory").is_err() {
+            return Ok(DirectoryConfig::default());
+        }
+        let config = figment
+            .extract_inner::<DirectoryConfig>("directory")
+            .map_err(|e| format!("Invalid directory configuration: {e}"))?;
+        if config.cron_interval == 0
+            || config.full_check_interval == 0
+            || config.checks_to_store == 0
+            || config.max_failures == 0
+            || config.timeout_seconds == 0
+            || config.api_max_top == 0
+            || config.degraded_after == 0
+            || config.max_concurrent_checks == 0
+            || config.uptimes_to_store == 0
+        {
+            return Err(
+                "Invalid directory configuration: cron_interval, full_check_interval, \
+                checks_to_store, max_failures, timeout_seconds, api_max_top, degraded_after, \
+                max_concurrent_checks and uptimes_to_store need to be at least 1."
+                    .into(),
+            );
+        }
+        if config
+            .admin_token
+            .as_ref()
+            .is_some_and(|token| token.len() < MIN_ADMIN_TOKEN_LENGTH)
+        {
+            return Err(format!(
+                "Invalid directory configuration: admin_token needs to be at least \
+                {MIN_ADMIN_TOKEN_LENGTH} characters long."
+            ));
+        }
+        if !(0..=100).contains(&config.forward_min_uptime) {
+            return Err(
+                "Invalid directory configuration: forward_min_uptime needs to be a percentage."
+                    .into(),
+            );
+        }
+        if !RATINGS
+            .iter()
+            .any(|(rating, _)| *rating == config.forward_min_rating)
+        {
+            return Err(format!(
+                "Invalid directory configuration: forward_min_rating needs to be one of {}.",
+                RATINGS.map(|(rating, _)| rating).join(", ")
+            ));
+        }
+        Ok(config)
+    }
+}
+
+// the pool enables the foreign key constraints on each new connection, so deleting an instance
+// cascades to its checks, scans and events
 #[database("directory")]
 pub struct DirectoryDbConn(SqliteConnection);
 
+impl DirectoryDbConn {
+    /// retrieve a connection outside of a request, i.e. for scheduled checks
+    pub async fn from_pool(
+        pool: &ConnectionPool<DirectoryDbConn, SqliteConnection>,
+    ) -> Option<DirectoryDbConn> {
+        pool.get().await.map(DirectoryDbConn)
+    }
+}
+
 #[allow(clippy::struct_excessive_bools)]
 #[derive(Clone, QueryableByName, Queryable, Serialize)]
 #[serde(crate = "rocket::serde")]
@@ -140,19 +400,49 @@ pub struct Instance {
     pub https: bool,
     pub https_redirect: bool,
     pub country_id: String,
+    #[diesel(sql_type = diesel::sql_types::Text, deserialize_as = String)]
+    #[serde(flatten)]
+    pub location: Location,
     pub attachments: bool,
     pub csp_header: bool,
+    #[serde(skip)]
+    pub csp_policy: String, // as sent by the instance, to explain a failed CSP check
+    #[diesel(embed)]
+    #[serde(flatten)]
+    pub features: InstanceFeatures,
     #[diesel(sql_type = diesel::sql_types::Integer)]
     pub uptime: i32,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub latency_p50: i32,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub latency_p95: i32,
     #[diesel(sql_type = diesel::sql_types::Text)]
     pub rating_mozilla_observatory: String,
+    #[diesel(sql_type = diesel::sql_types::Text, deserialize_as = String)]
+    pub ratings: Ratings,
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Integer>)]
+    pub cert_days: Option<i32>,
+    #[diesel(sql_type = diesel::sql_types::Text, deserialize_as = String)]
+    pub headers: HeaderChecks,
+    pub status: String,
+    pub failures: i32,
+    pub verified: bool,
+    pub display_name: String,
+    pub description: String,
+    pub contact: String,
 }
 
 impl Instance {
-    pub async fn check_up(&self) -> bool {
-        match request_head(&self.url).await {
-            Ok(res) => res.status() == StatusCode::OK,
-            Err(_) => false,
+    // returns if the instance is up, the HTTP status code, 0 if the request failed, and the
+    // latency, excluding the wait for the rate limits
+    pub async fn check_up(&self) -> (bool, u16, Duration) {
+        match timed_request(&self.url, Method::HEAD, &KEEPALIVE, Bytes::new()).await {
+            (Ok(res), elapsed) => (
+                res.status() == StatusCode::OK,
+                res.status().as_u16(),
+                elapsed,
+            ),
+            (Err(_), elapsed) => (false, 0, elapsed),
         }
     }
 
@@ -164,6 +454,257 @@ impl Instance {
             "\u{2718}".into() // Heavy Ballot X
         }
     }
+
+    #[must_use]
+    pub fn format_expiry(expiry: &str) -> String {
+        match expiry.find(|c: char| !c.is_ascii_digit()) {
+            Some(unit_start) if unit_start > 0 => {
+                format!("{} {}", &expiry[..unit_start], &expiry[unit_start..])
+            }
+            _ if expiry.is_empty() => "-".into(),
+            _ => expiry.into(),
+        }
+    }
+
+    #[must_use]
+    pub fn format_latency(latency: i32) -> String {
+        if latency > 0 {
+            format!("{latency} ms")
+        } else {
+            "-".into()
+        }
+    }
+}
+
+// ratings of all scanners, keyed on the scanner name
+#[derive(Clone, Default, Serialize)]
+#[serde(crate = "rocket::serde", transparent)]
+pub struct Ratings(pub BTreeMap<String, String>);
+
+impl Ratings {
+    #[must_use]
+    pub fn get(&self, scanner: &str) -> &str {
+        self.0.get(scanner).map_or("-", String::as_str)
+    }
+}
+
+// ratings get aggregated into a JSON object by the database
+impl From<String> for Ratings {
+    fn from(ratings: String) -> Self {
+        Ratings(json::from_str(&ratings).unwrap_or_default())
+    }
+}
+
+impl From<&[ScanNew]> for Ratings {
+    fn from(scans: &[ScanNew]) -> Self {
+        Ratings(
+            scans
+                .iter()
+                .map(|scan| (scan.scanner.clone(), scan.rating.clone()))
+                .collect(),
+        )
+    }
+}
+
+// security header evaluations, keyed on the header check name
+#[derive(Clone, Default, Serialize)]
+#[serde(crate = "rocket::serde", transparent)]
+pub struct HeaderChecks(pub BTreeMap<String, bool>);
+
+// header checks get aggregated into a JSON object by the database
+impl From<String> for HeaderChecks {
+    fn from(checks: String) -> Self {
+        HeaderChecks(json::from_str(&checks).unwrap_or_default())
+    }
+}
+
+impl From<&[HeaderNew]> for HeaderChecks {
+    fn from(checks: &[HeaderNew]) -> Self {
+        HeaderChecks(
+            checks
+                .iter()
+                .map(|check| (check.name.clone(), check.passed))
+                .collect(),
+        )
+    }
+}
+
+#[derive(Queryable, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct InstanceEvent {
+    pub updated: i64, // UNIX timestamp
+    pub field: String,
+    pub old_value: String,
+    pub new_value: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = instance_events)]
+pub struct InstanceEventNew {
+    pub field: String,
+    pub old_value: String,
+    pub new_value: String,
+    pub instance_id: i32,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = headers)]
+pub struct HeaderNew {
+    pub name: String,
+    pub passed: bool,
+    pub instance_id: i32,
+}
+
+impl HeaderNew {
+    #[must_use]
+    pub fn new(name: &str, passed: bool) -> HeaderNew {
+        HeaderNew {
+            name: name.into(),
+            passed,
+            instance_id: 0,
+        }
+    }
+}
+
+// differences between a Content-Security-Policy and the one recommended for the instance version
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CspComparison {
+    pub passed: bool,
+    pub policy: String,
+    pub rule: String, // version prefix of the recommendation compared against, empty if none applies
+    pub recommendation: String,
+    pub missing: Vec<String>,
+    pub extra: Vec<String>,
+    pub differing: Vec<CspDirectiveDiff>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CspDirectiveDiff {
+    pub directive: String,
+    pub missing: Vec<String>,
+    pub extra: Vec<String>,
+}
+
+impl CspComparison {
+    #[must_use]
+    pub fn new(policy: &str, version: &str, popups_optional: bool) -> CspComparison {
+        // versions before 1.0 didn't come with a CSP, so any policy earns a brownie point for trying
+        if version.starts_with("0.") {
+            return CspComparison {
+                passed: !policy.is_empty(),
+                policy: policy.into(),
+                rule: "0.".into(),
+                ..CspComparison::default()
+            };
+        }
+
+        let mut rules: Vec<(&str, &str)> = CSP_MAP
+            .iter()
+            .filter(|rule| version.starts_with(rule.0))
+            .copied()
+            .collect();
+        if rules.is_empty() {
+            rules.push(("", CSP_RECOMMENDATION));
+        }
+        let actual = Self::parse(policy);
+        let mut best: Option<CspComparison> = None;
+        for (rule, recommendation) in rules {
+            let mut expected = Self::parse(recommendation);
+            let mut candidates = vec![expected.clone()];
+            if popups_optional
+                && let Some(sandbox) = expected.get_mut("sandbox")
+                && sandbox.remove("allow-popups")
+            {
+                candidates.push(expected);
+            }
+            for candidate in candidates {
+                let comparison = Self::compare(&candidate, &actual, policy, rule, recommendation);
+                if best
+                    .as_ref()
+                    .is_none_or(|best| comparison.differences() < best.differences())
+                {
+                    best = Some(comparison);
+                }
+            }
+        }
+        let mut comparison = best.unwrap_or_default();
+        comparison.passed = Self::matches(policy, version, popups_optional);
+        comparison
+    }
+
+    // the check itself requires the policy to be exactly the recommended one of the version, the
+    // comparison above only serves to explain why it failed
+    fn matches(policy: &str, version: &str, popups_optional: bool) -> bool {
+        !policy.is_empty()
+            && CSP_MAP.iter().any(|rule| {
+                version.starts_with(rule.0)
+                    && (policy == rule.1
+                        || (popups_optional && policy == rule.1.replace(" allow-popups", "")))
+            })
+    }
+
+    fn differences(&self) -> usize {
+        self.missing.len() + self.extra.len() + self.differing.len()
+    }
+
+    // browsers ignore repeated directives and the order of sources, so the explanation does, too
+    fn parse(policy: &str) -> BTreeMap<String, BTreeSet<String>> {
+        let mut directives = BTreeMap::new();
+        for directive in policy.split(';') {
+            let mut tokens = directive.split_whitespace();
+            if let Some(name) = tokens.next() {
+                directives
+                    .entry(name.to_ascii_lowercase())
+                    .or_insert_with(|| tokens.map(str::to_ascii_lowercase).collect());
+            }
+        }
+        directives
+    }
+
+    fn compare(
+        expected: &BTreeMap<String, BTreeSet<String>>,
+        actual: &BTreeMap<String, BTreeSet<String>>,
+        policy: &str,
+        rule: &str,
+        recommendation: &str,
+    ) -> CspComparison {
+        let format = |name: &String, sources: &BTreeSet<String>| {
+            let mut directive = name.clone();
+            for source in sources {
+                directive.push(' ');
+                directive.push_str(source);
+            }
+            directive
+        };
+        let mut comparison = CspComparison {
+            passed: false,
+            policy: policy.into(),
+            rule: rule.into(),
+            recommendation: recommendation.into(),
+            ..CspComparison::default()
+        };
+        for (name, sources) in expected {
+            match actual.get(name) {
+                None => comparison.missing.push(format(name, sources)),
+                Some(actual_sources) if actual_sources != sources => {
+                    comparison.differing.push(CspDirectiveDiff {
+                        directive: name.clone(),
+                        missing: sources.difference(actual_sources).cloned().collect(),
+                        extra: actual_sources.difference(sources).cloned().collect(),
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+        for (name, sources) in actual {
+            if !expected.contains_key(name) {
+                comparison.extra.push(format(name, sources));
+            }
+        }
+        comparison
+    }
 }
 
 #[allow(clippy::struct_excessive_bools)]
@@ -179,14 +720,509 @@ pub struct InstanceNew {
     pub country_id: String,
     pub attachments: bool,
     pub csp_header: bool,
+    pub csp_policy: String,
+    #[diesel(embed)]
+    pub features: InstanceFeatures,
+}
+
+// configuration options of the instance, as advertised in its HTML
+#[allow(clippy::struct_excessive_bools)]
+#[derive(
+    AsChangeset, Clone, Debug, Default, Insertable, PartialEq, Eq, QueryableByName, Serialize,
+)]
+#[serde(crate = "rocket::serde")]
+#[diesel(table_name = instances)]
+pub struct InstanceFeatures {
+    pub discussion: bool,
+    pub password: bool,
+    pub burn_after_reading: bool, // selected by default
+    pub qr_code: bool,
+    pub expiration_options: String, // comma separated
+    pub expiration_default: String,
+    pub formatters: String, // comma separated
+    pub formatter_default: String,
+    pub languages: String, // comma separated, empty if the language selection is disabled
+    pub template: String,  // name of the template, empty if it couldn't be detected
+}
+
+impl InstanceFeatures {
+    // labels and values of all features, to compare and record them
+    #[must_use]
+    pub fn fields(&self) -> [(&'static str, String); 10] {
+        [
+            ("discussion", format!("{:?}", self.discussion)),
+            ("password", format!("{:?}", self.password)),
+            (
+                "burn_after_reading",
+                format!("{:?}", self.burn_after_reading),
+            ),
+            ("qr_code", format!("{:?}", self.qr_code)),
+            ("expiration_options", self.expiration_options.clone()),
+            ("expiration_default", self.expiration_default.clone()),
+            ("formatters", self.formatters.clone()),
+            ("formatter_default", self.formatter_default.clone()),
+            ("languages", self.languages.clone()),
+            ("template", self.template.clone()),
+        ]
+    }
+
+    // longest expiration option offered, "never" if pastes may be kept forever
+    #[must_use]
+    pub fn max_expiry(&self) -> Option<&str> {
+        self.expiration_options
+            .split(',')
+            .filter_map(|expiry| Some((expiry_to_seconds(expiry)?, expiry)))
+            .max_by_key(|(seconds, _)| *seconds)
+            .map(|(_, expiry)| expiry)
+    }
+}
+
+/// comma separated values of a filter, i.e. `DE,CH`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FilterList(pub Vec<String>);
+
+impl FilterList {
+    #[must_use]
+    pub fn any(&self, predicate: impl Fn(&str) -> bool) -> bool {
+        self.0.iter().any(|value| predicate(value))
+    }
+}
+
+impl<'v> FromFormField<'v> for FilterList {
+    fn from_value(field: ValueField<'v>) -> rocket::form::Result<'v, Self> {
+        Ok(FilterList(
+            field
+                .value
+                .split(',')
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(String::from)
+                .collect(),
+        ))
+    }
+}
+
+impl Serialize for FilterList {
+    fn serialize<S: rocket::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0.join(","))
+    }
+}
+
+/// boolean filter, a blank value, as sent by the "any" option of the filter form, fails to parse
+/// and so leaves the filter unset
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FilterFlag(pub bool);
+
+impl<'v> FromFormField<'v> for FilterFlag {
+    fn from_value(field: ValueField<'v>) -> rocket::form::Result<'v, Self> {
+        if field.value.is_empty() {
+            return Err(rocket::form::Error::validation("blank flag").into());
+        }
+        bool::from_value(field).map(FilterFlag)
+    }
+}
+
+impl Serialize for FilterFlag {
+    fn serialize<S: rocket::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bool(self.0)
+    }
+}
+
+/// filters of the instance list, unset ones match all instances, lists match if any of their
+/// values does and the negated filters, with names ending in "!", if none does
+#[derive(Clone, Debug, Default, FromForm, PartialEq, Eq, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct InstanceFilter {
+    pub attachments: Option<FilterFlag>,
+    pub country: Option<FilterList>,
+    #[field(name = "country!")]
+    #[serde(rename = "country!")]
+    pub country_not: Option<FilterList>,
+    pub region: Option<FilterList>,
+    #[field(name = "region!")]
+    #[serde(rename = "region!")]
+    pub region_not: Option<FilterList>,
+    pub csp_header: Option<FilterFlag>,
+    pub https: Option<FilterFlag>,
+    pub https_redirect: Option<FilterFlag>,
+    pub version: Option<FilterList>, // prefixes of the version
+    #[field(name = "version!")]
+    #[serde(rename = "version!")]
+    pub version_not: Option<FilterList>,
+    // sent as `version>=1.7`, the query gets split at the equals sign, leaving the field name
+    // without it
+    #[field(name = "version>")]
+    #[serde(rename = "version>")]
+    pub min_version: Option<String>,
+    #[field(name = "version<")]
+    #[serde(rename = "version<")]
+    pub max_version: Option<String>,
+    pub min_uptime: Option<u8>,
+    pub min_rating: Option<String>,
+    pub max_latency_ms: Option<u32>,
+    pub min_cert_days: Option<u16>,
+    pub discussion: Option<FilterFlag>,
+    pub password: Option<FilterFlag>,
+    pub qr_code: Option<FilterFlag>,
+    pub max_expiry: Option<String>,
+    pub template: Option<FilterList>,
+    pub exclude: Vec<FilterList>, // URLs, the parameter may be repeated
+}
+
+/// request guard, rejecting filters with unsupported comparison operators in their names, like
+/// `version>1.7`, which would otherwise get ignored and match all instances
+pub struct FilterOperators;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for FilterOperators {
+    type Error = String;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.query_fields().find(|field| {
+            let name = field.name.as_name().as_str();
+            name.contains(['<', '>']) && !["version>", "version<"].contains(&name)
+        }) {
+            Some(field) => Outcome::Error((
+                Status::BadRequest,
+                format!(
+                    "Unsupported filter {}, use version>= or version<= instead",
+                    field.name
+                ),
+            )),
+            None => Outcome::Success(FilterOperators),
+        }
+    }
+}
+
+impl InstanceFilter {
+    /// whether any of the version filters is set
+    #[must_use]
+    pub fn constrains_version(&self) -> bool {
+        self.version.is_some()
+            || self.version_not.is_some()
+            || self.min_version.is_some()
+            || self.max_version.is_some()
+    }
+
+    #[must_use]
+    pub fn matches(&self, instance: &Instance) -> bool {
+        let flag =
+            |filter: Option<FilterFlag>, value: bool| filter.is_none_or(|filter| filter.0 == value);
+        // empty lists, as sent by blank form fields, match all instances
+        let any = |filter: &Option<FilterList>, predicate: &dyn Fn(&str) -> bool| {
+            filter
+                .as_ref()
+                .is_none_or(|list| list.0.is_empty() || list.any(predicate))
+        };
+        let none = |filter: &Option<FilterList>, predicate: &dyn Fn(&str) -> bool| {
+            filter.as_ref().is_none_or(|list| !list.any(predicate))
+        };
+        let country = |country: &str| country.eq_ignore_ascii_case(&instance.country_id);
+        let region = |region: &str| in_region(&instance.country_id, region);
+        let version = |prefix: &str| instance.version.starts_with(prefix);
+        let min_uptime = i32::from(self.min_uptime.unwrap_or(0).min(100));
+        // unknown expiration options don't filter anything
+        let max_expiry = self.max_expiry.as_deref().and_then(expiry_to_seconds);
+
+        flag(self.attachments, instance.attachments)
+            && flag(self.csp_header, instance.csp_header)
+            && flag(self.https, instance.https)
+            && flag(self.https_redirect, instance.https_redirect)
+            && flag(self.discussion, instance.features.discussion)
+            && flag(self.password, instance.features.password)
+            && flag(self.qr_code, instance.features.qr_code)
+            && any(&self.country, &country)
+            && none(&self.country_not, &country)
+            && any(&self.region, &region)
+            && none(&self.region_not, &region)
+            && any(&self.version, &version)
+            && none(&self.version_not, &version)
+            && self.min_version.as_ref().is_none_or(|bound| {
+                compare_version(&instance.version, bound) != std::cmp::Ordering::Less
+            })
+            && self.max_version.as_ref().is_none_or(|bound| {
+                compare_version(&instance.version, bound) != std::cmp::Ordering::Greater
+            })
+            && any(&self.template, &|template| {
+                template == instance.features.template
+            })
+            && !self
+                .exclude
+                .iter()
+                .any(|list| list.any(|url| strip_url(url.into()) == instance.url))
+            && instance.uptime >= min_uptime
+            && self.min_rating.as_ref().is_none_or(|rating| {
+                rating_to_percent(&instance.rating_mozilla_observatory) >= rating_to_percent(rating)
+            })
+            && self.max_latency_ms.is_none_or(|max_latency| {
+                instance.latency_p50 != 0
+                    && i64::from(instance.latency_p50) <= i64::from(max_latency)
+            })
+            && self.min_cert_days.is_none_or(|min_cert_days| {
+                instance
+                    .cert_days
+                    .is_some_and(|cert_days| cert_days >= i32::from(min_cert_days))
+            })
+            && max_expiry.is_none_or(|max_expiry| {
+                instance
+                    .features
+                    .max_expiry()
+                    .and_then(expiry_to_seconds)
+                    .is_some_and(|expiry| expiry <= max_expiry)
+            })
+    }
+}
+
+// numerical parts of a version, so 1.10 comes after 1.9
+fn version_parts(version: &str) -> Vec<u16> {
+    version
+        .split('.')
+        .map(|part| part.parse::<u16>().unwrap_or_default())
+        .collect()
+}
+
+// compare a version only as precisely as the bound is given, so 1.7.3 is within 1.7
+fn compare_version(version: &str, bound: &str) -> std::cmp::Ordering {
+    let bound = version_parts(bound);
+    let mut version = version_parts(version);
+    version.resize(bound.len(), 0);
+    version.cmp(&bound)
+}
+
+/// sort keys of the paginated instance list
+#[derive(Clone, Copy, Debug, FromFormField, PartialEq, Eq, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum InstanceSort {
+    Uptime,
+    Rating,
+    Version,
+    Url,
+}
+
+impl InstanceSort {
+    pub const ALL: [InstanceSort; 4] = [
+        InstanceSort::Uptime,
+        InstanceSort::Rating,
+        InstanceSort::Version,
+        InstanceSort::Url,
+    ];
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            InstanceSort::Uptime => "uptime",
+            InstanceSort::Rating => "rating",
+            InstanceSort::Version => "version",
+            InstanceSort::Url => "url",
+        }
+    }
+
+    // best instances first, except for the alphabetical order
+    #[must_use]
+    pub fn default_order(self) -> SortOrder {
+        match self {
+            InstanceSort::Url => SortOrder::Asc,
+            _ => SortOrder::Desc,
+        }
+    }
+
+    /// ascending order of the instances, ties are broken by the URL, so pages are stable
+    #[must_use]
+    pub fn compare(self, a: &Instance, b: &Instance) -> std::cmp::Ordering {
+        match self {
+            InstanceSort::Uptime => a.uptime.cmp(&b.uptime),
+            InstanceSort::Rating => rating_to_percent(&a.rating_mozilla_observatory)
+                .cmp(&rating_to_percent(&b.rating_mozilla_observatory)),
+            InstanceSort::Version => version_parts(&a.version).cmp(&version_parts(&b.version)),
+            InstanceSort::Url => std::cmp::Ordering::Equal,
+        }
+        .then_with(|| a.url.cmp(&b.url))
+    }
+}
+
+#[derive(Clone, Copy, Debug, FromFormField, PartialEq, Eq, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub const ALL: [SortOrder; 2] = [SortOrder::Asc, SortOrder::Desc];
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+
+    #[must_use]
+    pub fn reverse(self) -> SortOrder {
+        match self {
+            SortOrder::Asc => SortOrder::Desc,
+            SortOrder::Desc => SortOrder::Asc,
+        }
+    }
+}
+
+/// one page of the sorted and filtered instance list, along with what produced it
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct InstanceList {
+    pub total: usize,  // matching instances, on all pages
+    pub offset: usize, // position of the first instance of this page
+    pub limit: u8,
+    pub sort: InstanceSort,
+    pub order: SortOrder,
+    pub filter: InstanceFilter,
+    pub next: Option<String>, // query of the following page, relative to this one
+    pub instances: Vec<Instance>,
 }
 
 pub struct InstancesCache {
     pub timeout: AtomicU64,
+    pub last_full_check: AtomicI64, // UNIX timestamp, 0 if none concluded yet
     pub instances: RwLock<Vec<Instance>>,
     pub negative_lookups: RwLock<HashMap<String, u64>>,
 }
 
+/// hexadecimal SHA-256 hash, as produced by the `sha256sum` command
+#[must_use]
+pub fn sha256_hex(input: &str) -> String {
+    ring::digest::digest(&ring::digest::SHA256, input.as_bytes())
+        .as_ref()
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+/// reasons for an instance failing to get checked, rendered as messages on the pages and as
+/// machine-readable codes in the API
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DirectoryError {
+    InvalidUrl(String),
+    UnsupportedHost(String),
+    Unreachable(String),
+    Timeout(String, u64),
+    HttpStatus(u16),
+    InvalidResponse,
+    NotPrivateBin(String),
+    RobotsDisallowed(String),
+    GeoIpUnavailable(String),
+    RecentlyFailed(u64), // seconds failed scans are cached for
+    UnknownInstance(String),
+    OwnershipUnverified(String),
+    FieldTooLong(&'static str, usize),
+    Blocklisted(String),
+}
+
+impl DirectoryError {
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        match self {
+            DirectoryError::InvalidUrl(_) => "invalid_url",
+            DirectoryError::UnsupportedHost(_) => "unsupported_host",
+            DirectoryError::Unreachable(_) => "unreachable",
+            DirectoryError::Timeout(_, _) => "timeout",
+            DirectoryError::HttpStatus(_) => "http_status",
+            DirectoryError::InvalidResponse => "invalid_response",
+            DirectoryError::NotPrivateBin(_) => "not_privatebin",
+            DirectoryError::RobotsDisallowed(_) => "robots_disallowed",
+            DirectoryError::GeoIpUnavailable(_) => "geoip_unavailable",
+            DirectoryError::RecentlyFailed(_) => "recently_failed",
+            DirectoryError::UnknownInstance(_) => "unknown_instance",
+            DirectoryError::OwnershipUnverified(_) => "ownership_unverified",
+            DirectoryError::FieldTooLong(_, _) => "field_too_long",
+            DirectoryError::Blocklisted(_) => "blocklisted",
+        }
+    }
+
+    // HTTP status of API responses reporting this error
+    #[must_use]
+    pub fn status(&self) -> rocket::http::Status {
+        use rocket::http::Status;
+        match self {
+            DirectoryError::InvalidUrl(_)
+            | DirectoryError::UnsupportedHost(_)
+            | DirectoryError::FieldTooLong(_, _) => Status::BadRequest,
+            DirectoryError::UnknownInstance(_) => Status::NotFound,
+            DirectoryError::OwnershipUnverified(_) | DirectoryError::Blocklisted(_) => {
+                Status::Forbidden
+            }
+            DirectoryError::RecentlyFailed(_) => Status::TooManyRequests,
+            DirectoryError::GeoIpUnavailable(_) => Status::InternalServerError,
+            _ => Status::UnprocessableEntity,
+        }
+    }
+}
+
+impl std::fmt::Display for DirectoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DirectoryError::InvalidUrl(url) => write!(f, "Not a valid URL: {url}"),
+            DirectoryError::UnsupportedHost(url) => {
+                write!(f, "Host or domain of URL {url} is not supported.")
+            }
+            DirectoryError::Unreachable(url) => {
+                write!(f, "Web server on URL {url} is not responding.")
+            }
+            DirectoryError::Timeout(url, seconds) => write!(
+                f,
+                "Web server on URL {url} is not responding within {seconds}s."
+            ),
+            DirectoryError::HttpStatus(status) => {
+                write!(f, "Web server responded with status code {status}.")
+            }
+            DirectoryError::InvalidResponse => write!(f, "Error reading the web server response."),
+            DirectoryError::NotPrivateBin(url) => {
+                write!(f, "The URL {url} doesn't seem to be a PrivateBin instance.")
+            }
+            DirectoryError::RobotsDisallowed(url) => write!(
+                f,
+                "Web server on URL {url} doesn't want to get added to the directory."
+            ),
+            DirectoryError::GeoIpUnavailable(path) => write!(
+                f,
+                "Error opening geo IP database {path} (defined in environment variable GEOIP_MMDB)."
+            ),
+            DirectoryError::RecentlyFailed(seconds) => write!(
+                f,
+                "A scan failed within the last {}.",
+                format_duration(*seconds)
+            ),
+            DirectoryError::UnknownInstance(url) => {
+                write!(f, "The URL {url} isn't listed in the directory.")
+            }
+            DirectoryError::OwnershipUnverified(url) => write!(
+                f,
+                "The secret doesn't match any hash served at {url}{OWNERSHIP_PATH}."
+            ),
+            DirectoryError::FieldTooLong(field, length) => {
+                write!(f, "The {field} can't be longer than {length} characters.")
+            }
+            DirectoryError::Blocklisted(url) => {
+                write!(f, "The domain of URL {url} is blocked from the directory.")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DirectoryError {}
+
+impl Serialize for DirectoryError {
+    fn serialize<S: rocket::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use rocket::serde::ser::SerializeStruct;
+        let mut error = serializer.serialize_struct("DirectoryError", 2)?;
+        error.serialize_field("error", self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
+
 struct LineReader<R> {
     reader: R,
     line_count: u16,
@@ -230,26 +1266,20 @@ impl<R: std::io::BufRead> Iterator for LineReader<R> {
     }
 }
 
-#[derive(Deserialize)]
-#[serde(crate = "rocket::serde")]
-struct ObservatoryScan<'r> {
-    error: Option<&'r str>,
-    grade: Option<&'r str>,
-    status_code: Option<u16>,
-}
-
 pub struct PrivateBin {
     pub instance: InstanceNew,
     pub scans: Vec<ScanNew>,
+    pub headers: Vec<HeaderNew>,
+    pub csp: CspComparison,
 }
 
 impl PrivateBin {
     /// # Errors
     ///
     /// Will return `Err` if `url` fails to get tested for any reason.
-    pub async fn new(url: String) -> Result<PrivateBin, String> {
+    pub async fn new(url: String) -> Result<PrivateBin, DirectoryError> {
         if !url.starts_with("http://") && !url.starts_with("https://") {
-            return Err(format!("Not a valid URL: {url}"));
+            return Err(DirectoryError::InvalidUrl(url));
         }
 
         let check_url = strip_url(url);
@@ -259,12 +1289,12 @@ impl PrivateBin {
 
         // remaining checks may run in parallel
         let check_properties = Self::check_properties(&check_url);
-        let check_rating = Self::check_rating_mozilla_observatory(&check_url);
+        let check_ratings = join_all(SCANNERS.iter().map(|scanner| scanner.scan(&check_url)));
         let country_code = Self::check_country(&check_url)?;
 
         // collect results of async checks
-        let (version, attachments, csp_header) = check_properties.await?;
-        let scans = vec![check_rating.await];
+        let (version, attachments, csp, headers, features) = check_properties.await?;
+        let scans = check_ratings.await;
 
         if !version.is_empty() {
             return Ok(PrivateBin {
@@ -276,29 +1306,31 @@ impl PrivateBin {
                     https_redirect,
                     country_id: country_code,
                     attachments,
-                    csp_header,
+                    csp_header: csp.passed,
+                    csp_policy: csp.policy.clone(),
+                    features,
                 },
                 scans,
+                headers,
+                csp,
             });
         }
-        Err(format!(
-            "The URL {check_url} doesn't seem to be a PrivateBin instance."
-        ))
+        Err(DirectoryError::NotPrivateBin(check_url))
     }
 
     // check country via geo IP database lookup
-    fn check_country(url: &str) -> Result<String, String> {
-        let mut country_code = "AQ".into();
+    fn check_country(url: &str) -> Result<String, DirectoryError> {
+        let mut country_code = UNKNOWN_COUNTRY.into();
         if let Ok(parsed_url) = Url::parse(url) {
             let ip: IpAddr;
             if let Some(host) = parsed_url.domain() {
                 let sockets = (host, 0).to_socket_addrs();
                 if sockets.is_err() {
-                    return Err(format!("Host or domain of URL {url} is not supported."));
+                    return Err(DirectoryError::UnsupportedHost(url.into()));
                 }
                 let socket = sockets.unwrap().next();
                 if socket.is_none() {
-                    return Err(format!("Host or domain of URL {url} is not supported."));
+                    return Err(DirectoryError::UnsupportedHost(url.into()));
                 }
                 ip = socket.unwrap().ip();
             } else if let Some(host) = parsed_url.host_str() {
@@ -310,13 +1342,16 @@ impl PrivateBin {
                 return Ok(country_code);
             }
 
+            // loopback addresses, i.e. of local test servers, can't be located
+            if ip.is_loopback() {
+                return Ok(country_code);
+            }
+
             let geoip_mmdb =
                 var("GEOIP_MMDB").expect("environment variable GEOIP_MMDB needs to be set");
             let opener = maxminddb::Reader::open_readfile(&geoip_mmdb);
             if opener.is_err() {
-                return Err(format!(
-                    "Error opening geo IP database {geoip_mmdb} (defined in environment variable GEOIP_MMDB)."
-                ));
+                return Err(DirectoryError::GeoIpUnavailable(geoip_mmdb));
             }
             let reader = opener.unwrap();
             if let Ok(country) = reader.lookup(ip)
@@ -335,7 +1370,7 @@ impl PrivateBin {
     }
 
     // check for HTTP to HTTPS redirect
-    async fn check_http(url: &str) -> Result<(bool, bool, String), String> {
+    async fn check_http(url: &str) -> Result<(bool, bool, String), DirectoryError> {
         let mut https = false;
         let mut https_redirect = false;
         let mut http_url = url.to_string();
@@ -372,11 +1407,11 @@ impl PrivateBin {
                     }
                 }
             }
-            Err(message) => {
+            Err(e) => {
                 // only emit an error if this server is reported as HTTP,
                 // HTTPS-only webservers, though uncommon, do enforce HTTPS
                 if url.starts_with("http://") {
-                    return Err(message);
+                    return Err(e);
                 }
                 https_redirect = true;
             }
@@ -384,13 +1419,24 @@ impl PrivateBin {
         Ok((https, https_redirect, resulting_url))
     }
 
-    // check version of privatebin / zerobin JS library, attachment support & CSP header
-    async fn check_properties(url: &str) -> Result<(String, bool, bool), String> {
-        let mut csp_header = false;
+    // check version of privatebin / zerobin JS library, attachment support, CSP, other security
+    // headers & configuration options
+    async fn check_properties(
+        url: &str,
+    ) -> Result<
+        (
+            String,
+            bool,
+            CspComparison,
+            Vec<HeaderNew>,
+            InstanceFeatures,
+        ),
+        DirectoryError,
+    > {
         let res = request(url, Method::GET, &CLOSE, Bytes::new()).await?;
         let status = res.status();
         if status != StatusCode::OK {
-            return Err(format!("Web server responded with status code {status}."));
+            return Err(DirectoryError::HttpStatus(status.as_u16()));
         }
 
         // collect Content-Security-Policy header
@@ -400,19 +1446,18 @@ impl PrivateBin {
         {
             csp.clone_into(&mut policy);
         }
+        let headers = Self::check_headers(res.headers());
 
         let mut version = String::new();
         let mut attachments = false;
-        let mut template = PrivateBinTemplate::Unknown;
+        let mut features = FeatureDetector::default();
         let Ok(body) = res.collect().await else {
-            return Err("Error reading the web server response.".to_owned());
+            return Err(DirectoryError::InvalidResponse);
         };
         let reader = LineReader {
             reader: body.aggregate().reader(),
             line_count: 0,
         };
-        let template_expression =
-            TEMPLATE_EXP.get_or_init(|| Regex::new(r"css/bootstrap(\d*)/").unwrap());
         let version_expression = VERSION_EXP.get_or_init(|| {
             Regex::new(r"js/(privatebin|zerobin).js\?(Alpha%20)?(\d+\.\d+\.*\d*)").unwrap()
         });
@@ -424,172 +1469,219 @@ impl PrivateBin {
 
             if !attachments && line_str.contains(" id=\"attachment\" ") {
                 attachments = true;
-                if !version.is_empty() && template != PrivateBinTemplate::Unknown {
-                    // we got version, template and attachment, stop parsing
-                    break;
-                }
-            }
-            if template == PrivateBinTemplate::Unknown
-                && let Some(matches) = template_expression.captures(&line_str)
-            {
-                template = if matches[1].is_empty() {
-                    PrivateBinTemplate::Bootstrap3
-                } else {
-                    PrivateBinTemplate::Bootstrap5
-                };
             }
+            features.parse(&line_str);
             if version.is_empty()
                 && let Some(matches) = version_expression.captures(&line_str)
             {
                 matches[3].clone_into(&mut version);
             }
         }
-        // check Content-Security-Policy header
-        if !policy.is_empty() {
-            for rule in CSP_MAP {
-                if version.starts_with(rule.0)
-                    && (policy.eq(rule.1)
-                        || (template != PrivateBinTemplate::Unknown // Bootstrap templates do not need popups
-                        && policy.eq(&rule.1.to_string().replace(" allow-popups", ""))))
-                {
-                    csp_header = true;
-                    break;
-                }
-            }
-            // versions before 1.0 didn't come with a CSP, so if we get to here, we'll give you a brownie point for trying
-            if version.starts_with("0.") {
-                csp_header = true;
-            }
-        }
-        Ok((version, attachments, csp_header))
+        // compare Content-Security-Policy header, Bootstrap templates do not need popups
+        let features = features.finish();
+        let csp = CspComparison::new(
+            &policy,
+            &version,
+            features.template.starts_with("bootstrap"),
+        );
+        Ok((version, attachments, csp, headers, features))
+    }
+
+    // evaluate the security headers, other than the CSP, of the instances response
+    fn check_headers(headers: &HeaderMap) -> Vec<HeaderNew> {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .trim()
+                .to_ascii_lowercase()
+        };
+        let hsts = header(STRICT_TRANSPORT_SECURITY.as_str());
+        let hsts_directives: Vec<&str> = hsts.split(';').map(str::trim).collect();
+        let hsts_max_age = hsts_directives
+            .iter()
+            .find_map(|directive| directive.strip_prefix("max-age="))
+            .and_then(|max_age| max_age.trim_matches('"').parse::<u64>().ok())
+            .unwrap_or_default();
+        // browsers apply the last policy they support, if several are given
+        let referrer_policy = header(REFERRER_POLICY.as_str());
+        let referrer_policy = referrer_policy
+            .rsplit(',')
+            .next()
+            .unwrap_or_default()
+            .trim();
+        vec![
+            HeaderNew::new(
+                "strict-transport-security",
+                hsts_max_age >= HSTS_MINIMUM_MAX_AGE,
+            ),
+            HeaderNew::new(
+                "strict-transport-security-include-subdomains",
+                hsts_directives.contains(&"includesubdomains"),
+            ),
+            HeaderNew::new(
+                "strict-transport-security-preload",
+                hsts_directives.contains(&"preload"),
+            ),
+            HeaderNew::new(
+                "referrer-policy",
+                [
+                    "no-referrer",
+                    "same-origin",
+                    "strict-origin",
+                    "strict-origin-when-cross-origin",
+                ]
+                .contains(&referrer_policy),
+            ),
+            HeaderNew::new(
+                "x-content-type-options",
+                header(X_CONTENT_TYPE_OPTIONS.as_str()) == "nosniff",
+            ),
+            HeaderNew::new(
+                "permissions-policy",
+                !header("permissions-policy").is_empty(),
+            ),
+            HeaderNew::new(
+                "cross-origin-opener-policy",
+                header("cross-origin-opener-policy") == "same-origin",
+            ),
+            HeaderNew::new(
+                "cross-origin-resource-policy",
+                ["same-origin", "same-site"]
+                    .contains(&header("cross-origin-resource-policy").as_str()),
+            ),
+            HeaderNew::new(
+                "cross-origin-embedder-policy",
+                ["require-corp", "credentialless"]
+                    .contains(&header("cross-origin-embedder-policy").as_str()),
+            ),
+        ]
     }
 
-    /// check rating at mozilla observatory
+    /// the operator proves the ownership of the host of an instance by serving the SHA-256 hash
+    /// of a secret, one per line, only known to them, at a well-known path
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// May panic in `res.collect().await.unwrap()`.
-    pub async fn check_rating_mozilla_observatory(url: &str) -> ScanNew {
-        if let Ok(parsed_url) = Url::parse(url)
-            && let Some(host) = parsed_url.host_str()
+    /// Will return `Err` if the hash of the secret isn't served by the host of `url`.
+    pub async fn verify_ownership(url: &str, secret: &str) -> Result<(), DirectoryError> {
+        let unverified = || {
+            let origin = Url::parse(url).map_or_else(
+                |_| url.into(),
+                |parsed_url| parsed_url.origin().ascii_serialization(),
+            );
+            DirectoryError::OwnershipUnverified(origin)
+        };
+        let Ok(ownership_url) = Url::parse(url).and_then(|url| url.join(OWNERSHIP_PATH)) else {
+            return Err(DirectoryError::InvalidUrl(url.into()));
+        };
+        if secret.is_empty() {
+            return Err(unverified());
+        }
+        let res = request_get(ownership_url.as_str()).await?;
+        if res.status() != StatusCode::OK {
+            return Err(unverified());
+        }
+        let Ok(body) = res.collect().await else {
+            return Err(DirectoryError::InvalidResponse);
+        };
+        let body = body.to_bytes();
+        let content = String::from_utf8_lossy(&body[..body.len().min(MAX_OWNERSHIP_SIZE)]);
+        let secret_hash = sha256_hex(secret);
+        if content
+            .lines()
+            .any(|line| line.trim().eq_ignore_ascii_case(&secret_hash))
         {
-            let observatory_url = format!("{OBSERVATORY_API}{host}");
-            for _retries in 0..5 {
-                // pause before scanning, to spread the load during full syncs
-                let backoff_ms = rand::rng().random_range(500..3000);
-                sleep(Duration::from_millis(backoff_ms)).await;
-                match request_post(&observatory_url).await {
-                    Ok(res) => {
-                        if res.status() == StatusCode::OK {
-                            let response_content_length = res
-                                .body()
-                                .size_hint()
-                                .upper()
-                                .unwrap_or(OBSERVATORY_MAX_CONTENT_LENGTH);
-                            // protect from malicious response
-                            if response_content_length >= OBSERVATORY_MAX_CONTENT_LENGTH {
-                                warn!(
-                                    "Failed retrieving observatory rating for {url} due response being too large (>= {OBSERVATORY_MAX_CONTENT_LENGTH})."
-                                );
-                                break;
-                            }
-                            let body_bytes = res.collect().await.unwrap().aggregate();
-                            if let Ok(api_response) =
-                                json::from_slice::<ObservatoryScan>(body_bytes.chunk())
-                            {
-                                if api_response.error.is_none()
-                                    && api_response.grade.is_some()
-                                    && api_response
-                                        .status_code
-                                        .unwrap_or(StatusCode::EXPECTATION_FAILED.as_u16())
-                                        == StatusCode::OK.as_u16()
-                                {
-                                    return ScanNew::new(
-                                        "mozilla_observatory",
-                                        api_response.grade.unwrap(),
-                                        0,
-                                    );
-                                }
-                                // initiate a rescan, if the error indicates a timeout
-                                // see: https://github.com/mdn/mdn-http-observatory/blob/main/src/api/errors.js
-                                let error = api_response.error.unwrap_or("");
-                                if error == "error-unknown" {
-                                    let backoff_ms = rand::rng().random_range(2000..5000);
-                                    sleep(Duration::from_millis(backoff_ms)).await;
-                                    continue;
-                                }
-                                warn!(
-                                    "Failed retrieving observatory rating for {url} due to {error}."
-                                );
-                            } else {
-                                warn!(
-                                    "Failed retrieving observatory rating for {url} due JSON decoding issue."
-                                );
-                            }
-                        } else {
-                            let status = res.status();
-                            if status == StatusCode::INTERNAL_SERVER_ERROR {
-                                let backoff_ms = rand::rng().random_range(2000..5000);
-                                sleep(Duration::from_millis(backoff_ms)).await;
-                                continue;
-                            }
-                            let status = res.status().as_u16();
-                            warn!(
-                                "Failed retrieving observatory rating for {url} due to HTTP status {status}."
-                            );
-                        }
-                    }
-                    _ => {
-                        warn!(
-                            "Failed retrieving observatory rating for {url} due request failing or timeout."
-                        );
-                    }
-                }
-                break;
-            }
+            return Ok(());
         }
-        ScanNew::default()
+        Err(unverified())
     }
 
-    // check robots.txt, if one exists, and bail if server doesn't want us to index the instance
-    async fn check_robots(url: &str) -> Result<bool, String> {
-        let robots_url = if url.ends_with('/') {
-            format!("{url}robots.txt")
-        } else {
-            format!("{url}/robots.txt")
+    // check robots.txt of the host, if one exists, and bail if it doesn't allow us to index the
+    // path of the instance
+    async fn check_robots(url: &str) -> Result<(), DirectoryError> {
+        let Ok(parsed_url) = Url::parse(url) else {
+            return Ok(());
         };
-        if let Ok(res) = request_get(&robots_url).await
-            && res.status() == StatusCode::OK
-        {
-            let mut rule_for_us = false;
-            let Ok(body) = res.collect().await else {
-                return Ok(true);
-            };
-            let reader = LineReader {
-                reader: body.aggregate().reader(),
-                line_count: 0,
-            };
-            for line in reader {
-                let Ok(line_str) = line else { break };
-
-                if rule_for_us {
-                    if line_str.starts_with("Disallow: /") {
-                        return Err(format!(
-                            "Web server on URL {url} doesn't want to get added to the directory."
-                        ));
+        let Ok(mut robots_url) = parsed_url.join("/robots.txt") else {
+            return Ok(());
+        };
+        for _redirect in 0..=MAX_ROBOTS_REDIRECTS {
+            // an unreachable robots.txt disallows everything (RFC 9309, section 2.3.1.4), but
+            // as the instance may just be down, report the error instead of the opt-out
+            let res = request_get(robots_url.as_str()).await?;
+            if res.status().is_redirection() {
+                match res
+                    .headers()
+                    .get(LOCATION)
+                    .and_then(|location| location.to_str().ok())
+                    .and_then(|location| robots_url.join(location).ok())
+                {
+                    Some(location) => {
+                        robots_url = location;
+                        continue;
                     }
-                    break;
-                } else if line_str.starts_with("User-agent: PrivateBinDirectoryBot") {
-                    rule_for_us = true;
+                    None => return Ok(()),
                 }
             }
+            // an unavailable robots.txt doesn't restrict anything
+            if res.status().is_client_error() {
+                return Ok(());
+            }
+            if !res.status().is_success() {
+                return Err(DirectoryError::HttpStatus(res.status().as_u16()));
+            }
+            // stop reading at the parsing limit
+            let mut body = res.into_body();
+            let mut content = Vec::new();
+            while content.len() < robots::MAX_SIZE {
+                match body.frame().await {
+                    Some(Ok(frame)) => {
+                        if let Ok(data) = frame.into_data() {
+                            content.extend_from_slice(&data);
+                        }
+                    }
+                    Some(Err(_)) => return Err(DirectoryError::InvalidResponse),
+                    None => break,
+                }
+            }
+            content.truncate(robots::MAX_SIZE);
+            let content = String::from_utf8_lossy(&content);
+            if RobotsTxt::parse(&content).is_allowed(robots::PRODUCT_TOKEN, parsed_url.path()) {
+                return Ok(());
+            }
+            return Err(DirectoryError::RobotsDisallowed(url.into()));
         }
-        Ok(true)
+        Ok(())
     }
 }
 
 #[tokio::test]
 async fn test_privatebin() {
+    use super::test_support::{Fixture, serve};
+
+    let url = serve("/privatebin", Fixture::privatebin("2.0.1"));
+    let test_url = url.clone();
+    let privatebin = PrivateBin::new(test_url).await.unwrap();
+    assert_eq!(privatebin.instance.url, url);
+    assert!(!privatebin.instance.https);
+    assert!(!privatebin.instance.https_redirect);
+    assert!(privatebin.instance.csp_header);
+    assert!(privatebin.instance.attachments);
+    assert_eq!(privatebin.instance.version, "2.0.1");
+    assert_eq!(privatebin.instance.country_id, "AQ");
+    assert_eq!(privatebin.instance.features.template, "bootstrap5");
+    assert_eq!(privatebin.instance.features.expiration_default, "1week");
+    assert_eq!(privatebin.scans[0].rating, "A+");
+    let checks = HeaderChecks::from(privatebin.headers.as_slice());
+    assert!(checks.0["x-content-type-options"]);
+    assert!(!checks.0["strict-transport-security"]);
+}
+
+#[tokio::test]
+#[ignore = "requires network access"]
+async fn test_privatebin_https() {
     let url = "https://privatebin.net".to_owned();
     let test_url = url.clone();
     let privatebin = PrivateBin::new(test_url).await.unwrap();
@@ -604,37 +1696,107 @@ async fn test_privatebin() {
 
 #[tokio::test]
 async fn test_url_rewrites() {
-    let components = ["https", "http"].iter().flat_map(|schema| {
-        ["/", "/?foo", "/#foo", "//", "/index.php"]
-            .iter()
-            .map(move |suffix| (schema, suffix))
-    });
-    for (schema, suffix) in components {
-        let url = format!("{schema}://privatebin.net{suffix}");
-        let privatebin = PrivateBin::new(url).await.unwrap();
-        assert_eq!(
-            privatebin.instance.url,
-            "https://privatebin.net".to_string()
-        );
+    use super::test_support::{Fixture, serve};
+
+    // served at the web root, as trailing slashes only get stripped there
+    let url = serve("/", Fixture::privatebin("2.0.1"));
+    let url = url.trim_end_matches('/');
+    for suffix in ["/", "/?foo", "/#foo", "//", "/index.php"] {
+        let privatebin = PrivateBin::new(format!("{url}{suffix}")).await.unwrap();
+        assert_eq!(privatebin.instance.url, url);
     }
 }
 
+#[tokio::test]
+async fn test_http_redirect() {
+    use super::test_support::{Fixture, serve};
+
+    let url = serve(
+        "/redirect",
+        Fixture::redirect("https://privatebin.example/"),
+    );
+    let (https, https_redirect, resulting_url) = PrivateBin::check_http(&url).await.unwrap();
+    assert!(https);
+    assert!(https_redirect);
+    assert_eq!(resulting_url, "https://privatebin.example");
+}
+
 #[tokio::test]
 async fn test_non_privatebin() {
-    let privatebin = PrivateBin::new("https://privatebin.info".into()).await;
-    assert!(privatebin.is_err());
+    use super::test_support::{Fixture, serve};
+
+    let url = serve(
+        "/homepage",
+        Fixture::html("<p>Not a PrivateBin instance.</p>"),
+    );
+    let privatebin = PrivateBin::new(url).await;
+    assert!(privatebin.is_err_and(|error| error.code() == "not_privatebin"));
 }
 
 #[tokio::test]
 async fn test_robots_txt() {
-    let privatebin = PrivateBin::new("http://zerobin-test.dssr.ch".into()).await;
-    assert!(privatebin.is_err());
+    use super::test_support::{Fixture, serve};
+
+    // robots.txt applies to the whole host, so it only restricts paths of this test
+    serve("/robots.txt", Fixture::redirect("/robots-moved.txt"));
+    serve(
+        "/robots-moved.txt",
+        Fixture::robots(
+            "User-agent: *\nDisallow: /\n\n\
+            User-agent: Googlebot\nuser-agent: privatebindirectorybot\n\
+            Disallow: /robots/ # all instances of this test\nAllow: /robots/allowed/\n",
+        ),
+    );
+    let url = serve("/robots/disallowed/", Fixture::privatebin("2.0.1"));
+    let privatebin = PrivateBin::new(url).await;
+    assert!(privatebin.is_err_and(|error| matches!(error, DirectoryError::RobotsDisallowed(_))));
+    let url = serve("/robots/allowed/", Fixture::privatebin("2.0.1"));
+    assert!(PrivateBin::new(url).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_robots_txt_errors() {
+    use super::test_support::{Fixture, serve_host};
+
+    // robots.txt applies to the whole host, so each case needs a server of its own
+    let url = serve_host(vec![
+        ("/", Fixture::privatebin("2.0.1")),
+        ("/robots.txt", Fixture::empty(StatusCode::NOT_FOUND)),
+    ]);
+    assert!(PrivateBin::new(url).await.is_ok());
+
+    // server errors disallow everything, without being an opt-out of the instance
+    let url = serve_host(vec![
+        ("/", Fixture::privatebin("2.0.1")),
+        (
+            "/robots.txt",
+            Fixture::empty(StatusCode::SERVICE_UNAVAILABLE),
+        ),
+    ]);
+    let privatebin = PrivateBin::new(url).await;
+    assert!(privatebin.is_err_and(|error| matches!(error, DirectoryError::HttpStatus(503))));
+
+    // rules beyond the parsing limit get ignored
+    let url = serve_host(vec![
+        ("/", Fixture::privatebin("2.0.1")),
+        (
+            "/robots.txt",
+            Fixture::robots(&format!(
+                "User-agent: *\n{}\nDisallow: /\n",
+                "#".repeat(robots::MAX_SIZE)
+            )),
+        ),
+    ]);
+    assert!(PrivateBin::new(url).await.is_ok());
 }
 
 #[tokio::test]
 async fn test_zerobin() {
-    let url = "http://zerobin-legacy.dssr.ch/".to_string();
-    let test_url = url.trim_end_matches('/').to_owned();
+    use super::test_support::{Fixture, serve};
+
+    // trailing slashes of paths are kept
+    let url = serve("/zerobin/", Fixture::zerobin());
+    let test_url = url.clone();
     let privatebin = PrivateBin::new(url).await.unwrap();
     assert_eq!(privatebin.instance.url, test_url);
     assert!(!privatebin.instance.https);
@@ -642,7 +1804,8 @@ async fn test_zerobin() {
     assert!(privatebin.instance.csp_header);
     assert_eq!(privatebin.instance.version, "0.20");
     assert!(!privatebin.instance.attachments);
-    assert_eq!(privatebin.instance.country_id, "CH");
+    assert_eq!(privatebin.instance.country_id, "AQ");
+    assert_eq!(privatebin.instance.features.template, "page");
 }
 
 /* disabled test, instance no longer exists and I couldn't find another one configured like this:
@@ -662,20 +1825,338 @@ async fn test_idn() {
     assert!(privatebin.is_err());
 }
 
-#[derive(PartialEq)]
+#[test]
+fn test_sha256_hex() {
+    assert_eq!(
+        sha256_hex("abc"),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+}
+
+#[test]
+fn test_directory_config() {
+    let config = DirectoryConfig::from_figment(&Figment::new()).unwrap();
+    assert_eq!(config.cron_interval, CRON_INTERVAL);
+    assert_eq!(config.api_max_top, 100);
+    assert_eq!(config.observatory_api, None);
+
+    let figment = Figment::new()
+        .merge(("directory.api_max_top", 20))
+        .merge(("directory.forward_min_rating", "B"));
+    let config = DirectoryConfig::from_figment(&figment).unwrap();
+    assert_eq!(config.api_max_top, 20);
+    assert_eq!(config.forward_min_rating, "B");
+    assert_eq!(config.max_failures, MAX_FAILURES);
+
+    let figment = Figment::new().merge(("directory.checks_to_store", 0));
+    assert!(DirectoryConfig::from_figment(&figment).is_err());
+    // no checks could ever run
+    let figment = Figment::new().merge(("directory.max_concurrent_checks", 0));
+    assert!(DirectoryConfig::from_figment(&figment).is_err());
+    // the uptimes would get deleted right after their aggregation
+    let figment = Figment::new().merge(("directory.uptimes_to_store", 0));
+    assert!(DirectoryConfig::from_figment(&figment).is_err());
+    let figment = Figment::new().merge(("directory.cron_interval", "hourly"));
+    assert!(DirectoryConfig::from_figment(&figment).is_err());
+    // would forward to no instance at all
+    let figment = Figment::new().merge(("directory.forward_min_rating", "A++"));
+    assert!(DirectoryConfig::from_figment(&figment).is_err());
+}
+
+#[test]
+fn test_feature_detection() {
+    // excerpts of the bootstrap5 template
+    let mut detector = FeatureDetector::default();
+    for line in [
+        r#"<link type="text/css" rel="stylesheet" href="css/bootstrap5/bootstrap-5.3.3.css" />"#,
+        r#"<select id="pasteExpiration" name="pasteExpiration" class="form-select">"#,
+        r#"<option value="5min">5 minutes</option>"#,
+        r#"<option value="1week" selected="selected">1 week</option>"#,
+        r#"<option value="never">Never</option>"#,
+        "</select>",
+        r#"<input class="form-check-input" type="checkbox" id="burnafterreading" name="burnafterreading" />"#,
+        r#"<input class="form-check-input" type="checkbox" id="opendiscussion" name="opendiscussion" checked="checked" />"#,
+        r#"<input type="password" id="passwordinput" placeholder="Password (recommended)" class="form-control" size="23" />"#,
+        r#"<select id="pasteFormatter" name="pasteFormatter" class="form-select">"#,
+        r#"<option value="plaintext" selected="selected">Plain Text</option>"#,
+        r#"<option value="markdown">Markdown</option>"#,
+        "</select>",
+        r#"<option value="ignored">an option outside the parsed selects</option>"#,
+    ] {
+        detector.parse(line);
+    }
+    let features = detector.finish();
+    assert!(features.discussion);
+    assert!(features.password);
+    assert!(!features.burn_after_reading);
+    assert!(!features.qr_code);
+    assert_eq!(features.expiration_options, "5min,1week,never");
+    assert_eq!(features.expiration_default, "1week");
+    assert_eq!(features.formatters, "plaintext,markdown");
+    assert_eq!(features.formatter_default, "plaintext");
+    assert!(features.languages.is_empty());
+    assert_eq!(features.template, "bootstrap5");
+    assert_eq!(features.max_expiry(), Some("never"));
+
+    // excerpts of the bootstrap-dark-page template, using dropdown menus
+    let mut detector = FeatureDetector::default();
+    for line in [
+        r#"<link type="text/css" rel="stylesheet" href="css/bootstrap/bootstrap-3.4.1.css" />"#,
+        r#"<link type="text/css" rel="stylesheet" href="css/bootstrap/darkstrap-0.9.3.css" />"#,
+        r#"<link type="text/css" rel="stylesheet" href="css/bootstrap/privatebin.css?1.7.6" />"#,
+        r#"<nav class="navbar navbar-inverse navbar-static-top">"#,
+        r#"<button id="newbutton" type="button" class="hidden navbar-btn btn btn-warning">"#,
+        r##"<li><a href="#" data-expiration="10min">10 minutes</a></li>"##,
+        r##"<li><a href="#" data-expiration="1month">1 month</a></li>"##,
+        r##"<li><a href="#" data-expiration="1day">1 day</a></li>"##,
+        r#"<input type="checkbox" id="burnafterreading" name="burnafterreading" checked="checked" />"#,
+        r#"<button id="qrcodelink" type="button" class="hidden btn btn-default navbar-btn">"#,
+        r##"<li><a href="#" data-format="syntaxhighlighting">Source Code</a></li>"##,
+        r##"<li><a href="#" data-lang="de">Deutsch (de)</a></li>"##,
+        r##"<li><a href="#" data-lang="en">English (en)</a></li>"##,
+    ] {
+        detector.parse(line);
+    }
+    let features = detector.finish();
+    assert!(!features.discussion);
+    assert!(!features.password);
+    assert!(features.burn_after_reading);
+    assert!(features.qr_code);
+    assert_eq!(features.expiration_options, "10min,1month,1day");
+    assert!(features.expiration_default.is_empty());
+    assert_eq!(features.formatters, "syntaxhighlighting");
+    assert_eq!(features.languages, "de,en");
+    assert_eq!(features.template, "bootstrap-dark-page");
+    assert_eq!(features.max_expiry(), Some("1month"));
+
+    let mut detector = FeatureDetector::default();
+    detector.parse(r#"<link type="text/css" rel="stylesheet" href="css/privatebin.css?1.7.6" />"#);
+    assert_eq!(detector.finish().template, "page");
+    assert_eq!(Instance::format_expiry("1month"), "1 month");
+    assert_eq!(Instance::format_expiry("never"), "never");
+    assert_eq!(Instance::format_expiry(""), "-");
+    assert_eq!(expiry_to_seconds("3months"), Some(7_776_000));
+    assert_eq!(expiry_to_seconds("forever"), None);
+}
+
+#[test]
+fn test_csp_comparison() {
+    let csp = CspComparison::new(CSP_RECOMMENDATION, "2.0.1", false);
+    assert!(csp.passed);
+    assert_eq!(csp.rule, "2.");
+    // the check requires the exact policy, but the differences ignore the order
+    let mut reordered: Vec<&str> = CSP_RECOMMENDATION.split("; ").collect();
+    reordered.reverse();
+    let csp = CspComparison::new(&reordered.join("; "), "2.0.1", false);
+    assert!(!csp.passed);
+    assert_eq!(csp.differences(), 0);
+    assert!(!CspComparison::new(&CSP_RECOMMENDATION.to_uppercase(), "2.0.1", false).passed);
+
+    let csp = CspComparison::new(
+        &(CSP_RECOMMENDATION
+            .replace("form-action 'none'; ", "")
+            .replace("style-src 'self'", "style-src 'self' 'unsafe-inline'")
+            .replace("object-src blob:", "object-src 'none'")
+            + "; upgrade-insecure-requests"),
+        "2.0.1",
+        false,
+    );
+    assert!(!csp.passed);
+    assert_eq!(csp.missing, vec!["form-action 'none'"]);
+    assert_eq!(csp.extra, vec!["upgrade-insecure-requests"]);
+    assert_eq!(csp.differing.len(), 2);
+    assert_eq!(csp.differing[0].directive, "object-src");
+    assert_eq!(csp.differing[0].missing, vec!["blob:"]);
+    assert_eq!(csp.differing[0].extra, vec!["'none'"]);
+    assert_eq!(csp.differing[1].directive, "style-src");
+    assert!(csp.differing[1].missing.is_empty());
+    assert_eq!(csp.differing[1].extra, vec!["'unsafe-inline'"]);
+
+    // the closest rule of the version gets picked
+    let csp = CspComparison::new(CSP_MAP[1].1, "1.7.6", false);
+    assert!(csp.passed);
+    assert_eq!(csp.rule, "1.7.6");
+    let without_popups = CSP_MAP[1].1.replace(" allow-popups", "");
+    assert!(!CspComparison::new(&without_popups, "1.7.6", false).passed);
+    assert!(CspComparison::new(&without_popups, "1.7.6", true).passed);
+
+    let csp = CspComparison::new("", "2.0.1", false);
+    assert!(!csp.passed);
+    assert_eq!(csp.missing.len(), 14);
+    assert!(CspComparison::new("default-src 'self'", "0.20", false).passed);
+    // versions without a recommendation fail, but get explained using the current one
+    let csp = CspComparison::new(CSP_RECOMMENDATION, "3.0.0", false);
+    assert!(!csp.passed);
+    assert_eq!(csp.rule, "");
+}
+
+#[test]
+fn test_security_headers() {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        STRICT_TRANSPORT_SECURITY,
+        "max-age=31536000; includeSubDomains".parse().unwrap(),
+    );
+    headers.insert(REFERRER_POLICY, "unsafe-url, no-referrer".parse().unwrap());
+    headers.insert(X_CONTENT_TYPE_OPTIONS, "nosniff".parse().unwrap());
+    headers.insert("cross-origin-opener-policy", "unsafe-none".parse().unwrap());
+    headers.insert(
+        "cross-origin-resource-policy",
+        "same-origin".parse().unwrap(),
+    );
+    let checks = HeaderChecks::from(PrivateBin::check_headers(&headers).as_slice());
+    assert!(checks.0["strict-transport-security"]);
+    assert!(checks.0["strict-transport-security-include-subdomains"]);
+    assert!(!checks.0["strict-transport-security-preload"]);
+    assert!(checks.0["referrer-policy"]);
+    assert!(checks.0["x-content-type-options"]);
+    assert!(!checks.0["permissions-policy"]);
+    assert!(!checks.0["cross-origin-opener-policy"]);
+    assert!(checks.0["cross-origin-resource-policy"]);
+    assert!(!checks.0["cross-origin-embedder-policy"]);
+
+    let short_hsts = HeaderMap::from_iter([(
+        STRICT_TRANSPORT_SECURITY,
+        "max-age=300; preload".parse().unwrap(),
+    )]);
+    let checks = HeaderChecks::from(PrivateBin::check_headers(&short_hsts).as_slice());
+    assert!(!checks.0["strict-transport-security"]);
+    assert!(checks.0["strict-transport-security-preload"]);
+}
+
+// collects the configuration options of an instance, while its HTML gets parsed line by line
+#[derive(Default)]
+struct FeatureDetector {
+    features: InstanceFeatures,
+    expiration_options: Vec<String>,
+    formatters: Vec<String>,
+    languages: Vec<String>,
+    select: &'static str, // the select element whose options are being parsed
+    template: PrivateBinTemplate,
+    dark: bool,
+    compact: bool,
+    page: bool,
+}
+
+impl FeatureDetector {
+    fn parse(&mut self, line: &str) {
+        let data_attribute_expression = DATA_ATTRIBUTE_EXP
+            .get_or_init(|| Regex::new(r#"data-(expiration|format|lang)="([^"]+)""#).unwrap());
+        let option_expression =
+            OPTION_EXP.get_or_init(|| Regex::new(r#"<option value="([^"]+)"([^>]*)>"#).unwrap());
+        let template_expression = TEMPLATE_EXP.get_or_init(|| {
+            Regex::new(r"css/(bootstrap(\d*)/|privatebin\.css|zerobin\.css)").unwrap()
+        });
+
+        if self.template == PrivateBinTemplate::Unknown
+            && let Some(matches) = template_expression.captures(line)
+        {
+            self.template = match matches.get(2) {
+                Some(major) if major.is_empty() => PrivateBinTemplate::Bootstrap3,
+                Some(_) => PrivateBinTemplate::Bootstrap5,
+                None => PrivateBinTemplate::Page,
+            };
+        }
+        // variants of the Bootstrap 3 template
+        if line.contains("css/bootstrap/darkstrap") {
+            self.dark = true;
+        }
+        if line.contains(" navbar-fixed-top") {
+            self.compact = true;
+        }
+        if line.contains(" id=\"newbutton\"") && line.contains("\"hidden navbar-btn btn ") {
+            self.page = true; // page variants order the button classes like the page template
+        }
+
+        if line.contains(" id=\"opendiscussion\"") {
+            self.features.discussion = true;
+        }
+        if line.contains(" id=\"passwordinput\"") {
+            self.features.password = true;
+        }
+        if line.contains(" id=\"burnafterreading\"") && line.contains(" checked") {
+            self.features.burn_after_reading = true;
+        }
+        if line.contains(" id=\"qrcodelink\"") {
+            self.features.qr_code = true;
+        }
+
+        // expiration & formatter options are offered as select and/or dropdown menu
+        if line.contains(" id=\"pasteExpiration\"") {
+            self.select = "expiration";
+        } else if line.contains(" id=\"pasteFormatter\"") {
+            self.select = "format";
+        }
+        for option in option_expression.captures_iter(line) {
+            let (options, default) = match self.select {
+                "expiration" => (
+                    &mut self.expiration_options,
+                    &mut self.features.expiration_default,
+                ),
+                "format" => (&mut self.formatters, &mut self.features.formatter_default),
+                _ => continue,
+            };
+            let value = option[1].to_string();
+            if option[2].contains("selected") {
+                value.clone_into(default);
+            }
+            if !options.contains(&value) {
+                options.push(value);
+            }
+        }
+        if line.contains("</select>") {
+            self.select = "";
+        }
+        for attribute in data_attribute_expression.captures_iter(line) {
+            let options = match &attribute[1] {
+                "expiration" => &mut self.expiration_options,
+                "format" => &mut self.formatters,
+                _ => &mut self.languages,
+            };
+            let value = attribute[2].to_string();
+            if !options.contains(&value) {
+                options.push(value);
+            }
+        }
+    }
+
+    fn finish(mut self) -> InstanceFeatures {
+        self.features.template = match self.template {
+            PrivateBinTemplate::Bootstrap3 => format!(
+                "bootstrap{}{}{}",
+                if self.dark { "-dark" } else { "" },
+                if self.compact { "-compact" } else { "" },
+                if self.page { "-page" } else { "" },
+            ),
+            PrivateBinTemplate::Bootstrap5 => "bootstrap5".into(),
+            PrivateBinTemplate::Page => "page".into(),
+            PrivateBinTemplate::Unknown => String::new(),
+        };
+        self.features.expiration_options = self.expiration_options.join(",");
+        self.features.formatters = self.formatters.join(",");
+        self.features.languages = self.languages.join(",");
+        self.features
+    }
+}
+
+#[derive(Default, PartialEq)]
 enum PrivateBinTemplate {
     Bootstrap3,
     Bootstrap5,
+    Page,
+    #[default]
     Unknown,
 }
 
-#[derive(Queryable)]
+#[derive(Queryable, Serialize)]
+#[serde(crate = "rocket::serde")]
 pub struct Scan {
     pub id: i32,
     pub scanner: String,
     pub rating: String,
     pub percent: i32,
     pub instance_id: i32,
+    pub details: String,
 }
 
 #[derive(Insertable, Clone)]
@@ -685,6 +2166,7 @@ pub struct ScanNew {
     pub rating: String,
     pub percent: i32,
     pub instance_id: i32,
+    pub details: String, // JSON encoded, scanner specific results
 }
 
 impl ScanNew {
@@ -696,16 +2178,11 @@ impl ScanNew {
             rating: rating.into(),
             percent,
             instance_id,
+            details: "{}".into(),
         }
     }
 }
 
-impl Default for ScanNew {
-    fn default() -> Self {
-        ScanNew::new("mozilla_observatory", "-", 0)
-    }
-}
-
 #[derive(Debug, PartialEq, Eq, Serialize)]
 #[serde(crate = "rocket::serde")]
 pub struct Page {
@@ -730,6 +2207,10 @@ pub struct InstancePage {
     pub topic: String,
     pub csp_recommendation: String,
     pub instance: Option<Instance>,
+    pub checks: Vec<CheckStatus>,
+    pub scans: Vec<Scan>,
+    pub events: Vec<InstanceEvent>,
+    pub csp: Option<CspComparison>,
     pub error: String,
 }
 
@@ -742,6 +2223,10 @@ impl InstancePage {
             topic,
             csp_recommendation: CSP_RECOMMENDATION.into(),
             instance,
+            checks: vec![],
+            scans: vec![],
+            events: vec![],
+            csp: None,
             error: error_string,
         }
     }
@@ -770,21 +2255,108 @@ impl StatusPage {
     }
 }
 
+/// the about page, describing the service as configured
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AboutPage {
+    pub title: String,
+    pub topic: String,
+    pub csp_recommendation: String,
+    pub version: String,
+    pub check_interval: String,
+    pub checks_to_store: u64,
+    pub checks_period: String,
+    pub uptimes_period: String,
+    pub api_max_top: u8,
+    pub forward_min_uptime: i32,
+    pub forward_min_rating: String,
+}
+
+impl AboutPage {
+    #[must_use]
+    pub fn new(topic: String, config: &DirectoryConfig) -> AboutPage {
+        AboutPage {
+            title: TITLE.into(),
+            topic,
+            csp_recommendation: CSP_RECOMMENDATION.into(),
+            version: env!("CARGO_PKG_VERSION").into(),
+            check_interval: format_duration(config.cron_interval),
+            checks_to_store: config.checks_to_store,
+            checks_period: format_duration(config.checks_to_store * config.cron_interval),
+            uptimes_period: format_duration(config.uptimes_to_store * 86400),
+            api_max_top: config.api_max_top,
+            forward_min_uptime: config.forward_min_uptime,
+            forward_min_rating: config.forward_min_rating.clone(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Serialize)]
 #[serde(crate = "rocket::serde")]
 pub struct TablePage {
     pub title: String,
     pub topic: String,
     pub tables: Vec<HtmlTable>,
+    pub last_full_check: Option<i64>,
+    pub groupings: Vec<PageLink>,
+    pub filter: InstanceFilter,
+    pub filter_reset: String, // link to the page without the filter, empty if none is set
+    pub view: Vec<(String, String)>, // grouping and sorting parameters, kept when filtering
+    pub shown: usize,
+    pub total: usize,
 }
 
 impl TablePage {
     #[must_use]
-    pub fn new(topic: String, tables: Vec<HtmlTable>) -> TablePage {
+    pub fn new(topic: String, tables: Vec<HtmlTable>, last_full_check: Option<i64>) -> TablePage {
         TablePage {
             title: TITLE.into(),
             topic,
             tables,
+            last_full_check,
+            groupings: vec![],
+            filter: InstanceFilter::default(),
+            filter_reset: String::new(),
+            view: vec![],
+            shown: 0,
+            total: 0,
+        }
+    }
+}
+
+/// link on a page, i.e. a sortable column header, plain text if the href is empty
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PageLink {
+    pub label: String,
+    pub href: String,
+    pub active: bool,
+}
+
+/// how the instances on the index page get split into tables
+#[derive(Clone, Copy, Debug, Default, FromFormField, PartialEq, Eq)]
+pub enum IndexGrouping {
+    #[default]
+    Version,
+    Region,
+}
+
+impl IndexGrouping {
+    pub const ALL: [IndexGrouping; 2] = [IndexGrouping::Version, IndexGrouping::Region];
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IndexGrouping::Version => "version",
+            IndexGrouping::Region => "region",
+        }
+    }
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            IndexGrouping::Version => "Version",
+            IndexGrouping::Region => "Region",
         }
     }
 }
@@ -793,11 +2365,40 @@ impl TablePage {
 #[serde(crate = "rocket::serde")]
 pub struct HtmlTable {
     pub title: String,
-    pub header: [String; 9],
-    pub body: Vec<[String; 10]>,
+    pub header: Vec<PageLink>,
+    pub body: Vec<[String; 15]>,
 }
 
 #[derive(Debug, FromForm)]
 pub struct AddForm {
     pub url: String,
 }
+
+// settings of an instance, that its operator can change after proving the ownership
+#[derive(Debug, FromForm)]
+pub struct OperatorForm {
+    pub url: String,
+    pub secret: String,
+    pub display_name: String,
+    pub description: String,
+    pub contact: String,
+    pub delisted: bool,
+}
+
+impl OperatorForm {
+    /// # Errors
+    ///
+    /// Will return `Err` if any of the fields exceeds the length stored in the database.
+    pub fn validate(&self) -> Result<(), DirectoryError> {
+        for (field, value, length) in [
+            ("display name", &self.display_name, 64),
+            ("description", &self.description, 255),
+            ("contact", &self.contact, 255),
+        ] {
+            if value.trim().chars().count() > length {
+                return Err(DirectoryError::FieldTooLong(field, length));
+            }
+        }
+        Ok(())
+    }
+}
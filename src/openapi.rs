@@ -0,0 +1,529 @@
+// OpenAPI 3 description of the JSON API, built from the same constants and configuration the
+// routes use, so limits and allowed values can't drift apart
+use super::functions::RATINGS;
+use super::models::{
+    DirectoryConfig, InstanceSort, InstanceStatus, SortOrder, TITLE, UptimeResolution,
+};
+use super::regions::{Alliance, Continent, UNKNOWN_REGION};
+use rocket::serde::json::{Value, json, serde_json};
+
+pub const OPENAPI_VERSION: &str = "3.0.3";
+
+/// the `OpenAPI` document of all JSON routes
+#[must_use]
+#[allow(clippy::too_many_lines)]
+pub fn document(config: &DirectoryConfig) -> Value {
+    json!({
+        "openapi": OPENAPI_VERSION,
+        "info": {
+            "title": format!("PrivateBin {TITLE}"),
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Lists PrivateBin instances, along with their properties, uptime and \
+                security ratings. Apart from this document, the routes require the HTTP header \
+                `Accept: application/json`.",
+        },
+        "paths": {
+            "/api": {
+                "get": {
+                    "operationId": "listInstances",
+                    "summary": "Random selection of listed instances, matching all given filters",
+                    "parameters": ([
+                        vec![query_parameter(
+                            "top",
+                            &format!(
+                                "Number of instances to return, larger values get capped at {}.",
+                                config.api_max_top
+                            ),
+                            &json!({
+                                "type": "integer",
+                                "minimum": 1,
+                                "maximum": 255,
+                                "default": 10,
+                            }),
+                        )],
+                        filter_parameters(),
+                    ].concat()),
+                    "responses": {
+                        "200": json_response("Matching instances, in random order", &json!({
+                            "type": "array",
+                            "items": { "$ref": "#/components/schemas/Instance" },
+                        })),
+                    },
+                },
+            },
+            "/api/v2/instances": {
+                "get": {
+                    "operationId": "pageInstances",
+                    "summary": "Sorted page of the listed instances, matching all given filters",
+                    "parameters": ([
+                        vec![
+                            query_parameter(
+                                "sort",
+                                "Property to sort the instances by, ties are sorted by URL.",
+                                &json!({
+                                    "type": "string",
+                                    "enum": InstanceSort::ALL
+                                        .iter()
+                                        .map(|sort| sort.as_str())
+                                        .collect::<Vec<_>>(),
+                                    "default": InstanceSort::Url.as_str(),
+                                }),
+                            ),
+                            query_parameter(
+                                "order",
+                                "Sort order, defaults to ascending for the URL and descending, \
+                                    that is best first, for everything else.",
+                                &json!({
+                                    "type": "string",
+                                    "enum": SortOrder::ALL
+                                        .iter()
+                                        .map(|order| order.as_str())
+                                        .collect::<Vec<_>>(),
+                                }),
+                            ),
+                            query_parameter(
+                                "offset",
+                                "Number of matching instances to skip.",
+                                &json!({ "type": "integer", "minimum": 0, "default": 0 }),
+                            ),
+                            query_parameter(
+                                "limit",
+                                &format!(
+                                    "Number of instances per page, larger values get capped at \
+                                    {}.",
+                                    config.api_max_top
+                                ),
+                                &json!({
+                                    "type": "integer",
+                                    "minimum": 1,
+                                    "maximum": 255,
+                                    "default": config.api_max_top,
+                                }),
+                            ),
+                        ],
+                        filter_parameters(),
+                    ].concat()),
+                    "responses": {
+                        "200": json_response("Page of matching instances", &json!({
+                            "$ref": "#/components/schemas/InstanceList",
+                        })),
+                    },
+                },
+            },
+            "/api/check": {
+                "get": {
+                    "operationId": "checkInstance",
+                    "summary": "Properties of a listed instance, or of an unlisted one after \
+                        scanning it",
+                    "parameters": [
+                        {
+                            "name": "url",
+                            "in": "query",
+                            "required": true,
+                            "description": "URL of the instance, including the scheme.",
+                            "schema": { "type": "string" },
+                        },
+                    ],
+                    "responses": {
+                        "200": json_response("The instance", &json!({
+                            "$ref": "#/components/schemas/Instance",
+                        })),
+                        "400": error_response("Invalid or unsupported URL"),
+                        "422": error_response("The URL isn't a reachable PrivateBin instance"),
+                        "429": error_response("A scan of the URL failed recently"),
+                        "500": error_response("The geo IP database is unavailable"),
+                    },
+                },
+            },
+            "/api/instance/{instance}/uptime": {
+                "get": {
+                    "operationId": "instanceUptime",
+                    "summary": "Uptime history of an instance, oldest period first",
+                    "parameters": [
+                        instance_parameter(),
+                        {
+                            "name": "from",
+                            "in": "query",
+                            "description": "Start of the range as UNIX timestamp, defaults to \
+                                30 days before its end.",
+                            "schema": { "type": "integer", "format": "int64" },
+                        },
+                        {
+                            "name": "to",
+                            "in": "query",
+                            "description": "End of the range as UNIX timestamp, defaults to now.",
+                            "schema": { "type": "integer", "format": "int64" },
+                        },
+                        {
+                            "name": "resolution",
+                            "in": "query",
+                            "description": "Length of the aggregated periods.",
+                            "schema": {
+                                "type": "string",
+                                "enum": UptimeResolution::ALL
+                                    .iter()
+                                    .map(|resolution| resolution.as_str())
+                                    .collect::<Vec<_>>(),
+                                "default": UptimeResolution::Day.as_str(),
+                            },
+                        },
+                    ],
+                    "responses": {
+                        "200": json_response("Uptime per period", &json!({
+                            "type": "array",
+                            "items": { "$ref": "#/components/schemas/Uptime" },
+                        })),
+                        "404": { "description": "Unknown instance" },
+                        "500": { "description": "Database error" },
+                    },
+                },
+            },
+            "/api/instance/{instance}/events": {
+                "get": {
+                    "operationId": "instanceEvents",
+                    "summary": "Recorded changes of an instance, oldest first",
+                    "parameters": [
+                        instance_parameter(),
+                        {
+                            "name": "field",
+                            "in": "query",
+                            "description": "Only changes of this field, i.e. `version` or \
+                                `rating_mozilla_observatory`.",
+                            "schema": { "type": "string" },
+                        },
+                    ],
+                    "responses": {
+                        "200": json_response("Changes of the instance", &json!({
+                            "type": "array",
+                            "items": { "$ref": "#/components/schemas/InstanceEvent" },
+                        })),
+                        "404": { "description": "Unknown instance" },
+                    },
+                },
+            },
+            "/api/openapi.json": {
+                "get": {
+                    "operationId": "openApi",
+                    "summary": "This description of the API",
+                    "responses": {
+                        "200": json_response("OpenAPI document", &json!({ "type": "object" })),
+                    },
+                },
+            },
+        },
+        "components": {
+            "schemas": {
+                "Instance": instance_schema(),
+                "InstanceList": instance_list_schema(),
+                "Uptime": object_schema(&json!({
+                    "period": { "type": "integer", "format": "int64", "description": "Start of \
+                        the period as UNIX timestamp" },
+                    "checks": { "type": "integer", "description": "Checks performed during the \
+                        period" },
+                    "uptime": { "type": "integer", "description": "Percentage of successful \
+                        checks" },
+                })),
+                "InstanceEvent": object_schema(&json!({
+                    "updated": { "type": "integer", "format": "int64", "description": "Time of \
+                        the change as UNIX timestamp" },
+                    "field": { "type": "string" },
+                    "old_value": { "type": "string" },
+                    "new_value": { "type": "string" },
+                })),
+                "Error": object_schema(&json!({
+                    "error": { "type": "string", "description": "Machine-readable code, i.e. \
+                        `not_privatebin`" },
+                    "message": { "type": "string", "description": "Human-readable explanation" },
+                })),
+            },
+        },
+    })
+}
+
+// filters shared by both versions of the instance list
+#[allow(clippy::too_many_lines)]
+fn filter_parameters() -> Vec<Value> {
+    let string = json!({ "type": "string" });
+    vec![
+        boolean_filter("attachments", "Only instances that allow file uploads."),
+        query_parameter(
+            "country",
+            "Only instances hosted in one of these comma separated countries, given by their \
+            two-letter ISO 3166 code, i.e. `DE,CH`.",
+            &string,
+        ),
+        query_parameter(
+            "country!",
+            "Only instances not hosted in any of these comma separated countries, i.e. \
+            `country!=US`.",
+            &string,
+        ),
+        query_parameter(
+            "region",
+            "Only instances hosted in one of these comma separated regions.",
+            &region_list(),
+        ),
+        query_parameter(
+            "region!",
+            "Only instances not hosted in any of these comma separated regions.",
+            &region_list(),
+        ),
+        boolean_filter(
+            "csp_header",
+            "Only instances sending the recommended Content-Security-Policy.",
+        ),
+        boolean_filter("https", "Only instances supporting HTTPS."),
+        boolean_filter("https_redirect", "Only instances redirecting to HTTPS."),
+        query_parameter(
+            "version",
+            "Only instances whose version starts with one of these comma separated prefixes, \
+            i.e. `1.` for all 1.x releases.",
+            &string,
+        ),
+        query_parameter(
+            "version!",
+            "Only instances whose version starts with none of these comma separated prefixes.",
+            &string,
+        ),
+        query_parameter(
+            "version>",
+            "Only instances of at least this version, compared as precisely as given, sent as \
+            `version>=1.7`.",
+            &string,
+        ),
+        query_parameter(
+            "version<",
+            "Only instances of at most this version, compared as precisely as given, so `1.7` \
+            includes 1.7.3, sent as `version<=1.7`.",
+            &string,
+        ),
+        query_parameter(
+            "min_uptime",
+            "Only instances with at least this uptime percentage, larger values get capped at \
+            100.",
+            &json!({ "type": "integer", "minimum": 0, "maximum": 255 }),
+        ),
+        query_parameter(
+            "min_rating",
+            "Only instances rated at least this well by the Mozilla Observatory.",
+            &json!({
+                "type": "string",
+                "enum": RATINGS.iter().map(|(rating, _)| *rating).collect::<Vec<_>>(),
+            }),
+        ),
+        query_parameter(
+            "max_latency_ms",
+            "Only instances with a median latency of at most this many milliseconds, instances \
+            without a measurement are excluded.",
+            &json!({ "type": "integer", "minimum": 0 }),
+        ),
+        query_parameter(
+            "min_cert_days",
+            "Only instances whose TLS certificate stays valid for at least this many days.",
+            &json!({ "type": "integer", "minimum": 0, "maximum": 65535 }),
+        ),
+        boolean_filter("discussion", "Only instances offering discussions."),
+        boolean_filter("password", "Only instances offering password protection."),
+        boolean_filter("qr_code", "Only instances offering QR codes."),
+        query_parameter(
+            "max_expiry",
+            "Only instances whose longest expiration option doesn't exceed this one, i.e. \
+            `1week`, instances keeping pastes forever are excluded unless set to `never`. Unknown \
+            values are ignored.",
+            &string,
+        ),
+        query_parameter(
+            "template",
+            "Only instances using one of the templates with these comma separated names, i.e. \
+            `bootstrap5`.",
+            &string,
+        ),
+        json!({
+            "name": "exclude",
+            "in": "query",
+            "description": "Instances not to return, given by their comma separated URLs, the \
+                parameter may be repeated.",
+            "schema": { "type": "array", "items": string },
+            "explode": true,
+        }),
+    ]
+}
+
+// the regions aren't validated, but unknown ones contain no countries
+fn region_list() -> Value {
+    let regions: Vec<&str> = Continent::ALL
+        .iter()
+        .map(|continent| continent.as_str())
+        .chain(Alliance::ALL.iter().map(|alliance| alliance.as_str()))
+        .chain([UNKNOWN_REGION])
+        .collect();
+    json!({
+        "type": "string",
+        "description": format!("Comma separated list of: {}", regions.join(", ")),
+    })
+}
+
+fn instance_list_schema() -> Value {
+    // the filters get echoed, unset ones as null
+    let filter: serde_json::Map<String, Value> = filter_parameters()
+        .into_iter()
+        .map(|parameter| {
+            let mut schema = parameter["schema"].clone();
+            schema["nullable"] = Value::Bool(true);
+            (
+                parameter["name"].as_str().unwrap_or_default().to_string(),
+                schema,
+            )
+        })
+        .collect();
+    object_schema(&json!({
+        "total": { "type": "integer", "description": "Matching instances, on all pages" },
+        "offset": { "type": "integer", "description": "Position of the first instance of the \
+            page" },
+        "limit": { "type": "integer" },
+        "sort": {
+            "type": "string",
+            "enum": InstanceSort::ALL.iter().map(|sort| sort.as_str()).collect::<Vec<_>>(),
+        },
+        "order": {
+            "type": "string",
+            "enum": SortOrder::ALL.iter().map(|order| order.as_str()).collect::<Vec<_>>(),
+        },
+        "filter": object_schema(&Value::Object(filter)),
+        "next": { "type": "string", "nullable": true, "description": "Query of the following \
+            page, relative to the current one, null on the last page" },
+        "instances": {
+            "type": "array",
+            "items": { "$ref": "#/components/schemas/Instance" },
+        },
+    }))
+}
+
+fn instance_schema() -> Value {
+    let string = json!({ "type": "string" });
+    let boolean = json!({ "type": "boolean" });
+    let integer = json!({ "type": "integer" });
+    object_schema(&json!({
+        "id": integer,
+        "url": string,
+        "version": string,
+        "https": boolean,
+        "https_redirect": boolean,
+        "country_id": { "type": "string", "description": "Two-letter ISO 3166 country code" },
+        "region": {
+            "type": "string",
+            "description": "Continent of the country, empty if unknown",
+            "enum": Continent::ALL
+                .iter()
+                .map(|continent| continent.as_str())
+                .chain([""])
+                .collect::<Vec<_>>(),
+        },
+        "jurisdiction": {
+            "type": "array",
+            "description": "Unions and intelligence sharing agreements the country belongs to",
+            "items": {
+                "type": "string",
+                "enum": Alliance::ALL
+                    .iter()
+                    .map(|alliance| alliance.as_str())
+                    .collect::<Vec<_>>(),
+            },
+        },
+        "attachments": boolean,
+        "csp_header": boolean,
+        "discussion": boolean,
+        "password": boolean,
+        "burn_after_reading": { "type": "boolean", "description": "Selected by default" },
+        "qr_code": boolean,
+        "expiration_options": { "type": "string", "description": "Comma separated" },
+        "expiration_default": string,
+        "formatters": { "type": "string", "description": "Comma separated" },
+        "formatter_default": string,
+        "languages": { "type": "string", "description": "Comma separated, empty if the \
+            language selection is disabled" },
+        "template": { "type": "string", "description": "Empty if it couldn't be detected" },
+        "uptime": { "type": "integer", "description": "Percentage of successful uptime checks" },
+        "latency_p50": { "type": "integer", "description": "Median latency in milliseconds, 0 \
+            if not measured" },
+        "latency_p95": { "type": "integer", "description": "95th percentile of the latency in \
+            milliseconds, 0 if not measured" },
+        "rating_mozilla_observatory": string,
+        "ratings": {
+            "type": "object",
+            "description": "Ratings keyed on the scanner name",
+            "additionalProperties": string,
+        },
+        "cert_days": { "type": "integer", "nullable": true, "description": "Days until the \
+            TLS certificate expires" },
+        "headers": {
+            "type": "object",
+            "description": "Security header checks keyed on their name, whether they passed",
+            "additionalProperties": boolean,
+        },
+        "status": {
+            "type": "string",
+            "enum": InstanceStatus::ALL
+                .iter()
+                .map(|status| status.as_str())
+                .collect::<Vec<_>>(),
+        },
+        "failures": { "type": "integer", "description": "Consecutive failed uptime checks" },
+        "verified": { "type": "boolean", "description": "Whether the operator proved the \
+            ownership" },
+        "display_name": string,
+        "description": string,
+        "contact": string,
+    }))
+}
+
+// objects of the API always contain all of their properties and nothing else
+fn object_schema(properties: &Value) -> Value {
+    let required: Vec<&String> = properties
+        .as_object()
+        .map(|properties| properties.keys().collect())
+        .unwrap_or_default();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+fn query_parameter(name: &str, description: &str, schema: &Value) -> Value {
+    json!({
+        "name": name,
+        "in": "query",
+        "description": description,
+        "schema": schema,
+    })
+}
+
+fn boolean_filter(name: &str, description: &str) -> Value {
+    query_parameter(name, description, &json!({ "type": "boolean" }))
+}
+
+fn instance_parameter() -> Value {
+    json!({
+        "name": "instance",
+        "in": "path",
+        "required": true,
+        "description": "ID of the instance, as returned by the list.",
+        "schema": { "type": "integer" },
+    })
+}
+
+fn json_response(description: &str, schema: &Value) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": schema } },
+    })
+}
+
+fn error_response(description: &str) -> Value {
+    json_response(
+        description,
+        &json!({ "$ref": "#/components/schemas/Error" }),
+    )
+}
@@ -0,0 +1,282 @@
+// grouping of the countries instances are hosted in, keyed on their ISO 3166 alpha-2 code
+use isocountry::CountryCode;
+use rocket::serde::Serialize;
+
+pub const UNKNOWN_COUNTRY: &str = "AQ"; // placeholder for instances that couldn't be located
+pub const UNKNOWN_REGION: &str = "unknown"; // selects the instances that couldn't be located
+
+/// continents following the UN geoscheme, with the Americas split into north, including central
+/// America and the Caribbean, and south
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Continent {
+    Africa,
+    Antarctica,
+    Asia,
+    Europe,
+    NorthAmerica,
+    Oceania,
+    SouthAmerica,
+}
+
+impl Continent {
+    pub const ALL: [Continent; 7] = [
+        Continent::Africa,
+        Continent::Antarctica,
+        Continent::Asia,
+        Continent::Europe,
+        Continent::NorthAmerica,
+        Continent::Oceania,
+        Continent::SouthAmerica,
+    ];
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Continent::Africa => "africa",
+            Continent::Antarctica => "antarctica",
+            Continent::Asia => "asia",
+            Continent::Europe => "europe",
+            Continent::NorthAmerica => "north-america",
+            Continent::Oceania => "oceania",
+            Continent::SouthAmerica => "south-america",
+        }
+    }
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Continent::Africa => "Africa",
+            Continent::Antarctica => "Antarctica",
+            Continent::Asia => "Asia",
+            Continent::Europe => "Europe",
+            Continent::NorthAmerica => "North America",
+            Continent::Oceania => "Oceania",
+            Continent::SouthAmerica => "South America",
+        }
+    }
+
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Continent> {
+        Continent::ALL
+            .into_iter()
+            .find(|continent| continent.as_str().eq_ignore_ascii_case(name))
+    }
+
+    /// continent of the country, none for unknown country codes and the placeholder of instances
+    /// that couldn't be located, which would otherwise end up in Antarctica
+    #[must_use]
+    pub fn of(country_code: &str) -> Option<Continent> {
+        if country_code.eq_ignore_ascii_case(UNKNOWN_COUNTRY) {
+            return None;
+        }
+        let country = CountryCode::for_alpha2_caseless(country_code).ok()?;
+        Continent::ALL
+            .into_iter()
+            .find(|continent| continent.countries().contains(&country.alpha2()))
+    }
+
+    fn countries(self) -> &'static [&'static str] {
+        match self {
+            Continent::Africa => &[
+                "AO", "BF", "BI", "BJ", "BW", "CD", "CF", "CG", "CI", "CM", "CV", "DJ", "DZ", "EG",
+                "EH", "ER", "ET", "GA", "GH", "GM", "GN", "GQ", "GW", "IO", "KE", "KM", "LR", "LS",
+                "LY", "MA", "MG", "ML", "MR", "MU", "MW", "MZ", "NA", "NE", "NG", "RE", "RW", "SC",
+                "SD", "SH", "SL", "SN", "SO", "SS", "ST", "SZ", "TD", "TG", "TN", "TZ", "UG", "YT",
+                "ZA", "ZM", "ZW",
+            ],
+            Continent::Antarctica => &["AQ", "BV", "GS", "HM", "TF"],
+            Continent::Asia => &[
+                "AE", "AF", "AM", "AZ", "BD", "BH", "BN", "BT", "CN", "CY", "GE", "HK", "ID", "IL",
+                "IN", "IQ", "IR", "JO", "JP", "KG", "KH", "KP", "KR", "KW", "KZ", "LA", "LB", "LK",
+                "MM", "MN", "MO", "MV", "MY", "NP", "OM", "PH", "PK", "PS", "QA", "SA", "SG", "SY",
+                "TH", "TJ", "TL", "TM", "TR", "TW", "UZ", "VN", "YE",
+            ],
+            Continent::Europe => &[
+                "AD", "AL", "AT", "AX", "BA", "BE", "BG", "BY", "CH", "CZ", "DE", "DK", "EE", "ES",
+                "FI", "FO", "FR", "GB", "GG", "GI", "GR", "HR", "HU", "IE", "IM", "IS", "IT", "JE",
+                "LI", "LT", "LU", "LV", "MC", "MD", "ME", "MK", "MT", "NL", "NO", "PL", "PT", "RO",
+                "RS", "RU", "SE", "SI", "SJ", "SK", "SM", "UA", "VA",
+            ],
+            Continent::NorthAmerica => &[
+                "AG", "AI", "AW", "BB", "BL", "BM", "BQ", "BS", "BZ", "CA", "CR", "CU", "CW", "DM",
+                "DO", "GD", "GL", "GP", "GT", "HN", "HT", "JM", "KN", "KY", "LC", "MF", "MQ", "MS",
+                "MX", "NI", "PA", "PM", "PR", "SV", "SX", "TC", "TT", "US", "VC", "VG", "VI",
+            ],
+            Continent::Oceania => &[
+                "AS", "AU", "CC", "CK", "CX", "FJ", "FM", "GU", "KI", "MH", "MP", "NC", "NF", "NR",
+                "NU", "NZ", "PF", "PG", "PN", "PW", "SB", "TK", "TO", "TV", "UM", "VU", "WF", "WS",
+            ],
+            Continent::SouthAmerica => &[
+                "AR", "BO", "BR", "CL", "CO", "EC", "FK", "GF", "GY", "PE", "PY", "SR", "UY", "VE",
+            ],
+        }
+    }
+}
+
+/// unions and intelligence sharing agreements, whose members share legislation or may share
+/// the data of the instances hosted in them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Alliance {
+    EuropeanUnion,
+    EuropeanEconomicArea,
+    FiveEyes,
+    NineEyes,
+    FourteenEyes,
+}
+
+impl Alliance {
+    pub const ALL: [Alliance; 5] = [
+        Alliance::EuropeanUnion,
+        Alliance::EuropeanEconomicArea,
+        Alliance::FiveEyes,
+        Alliance::NineEyes,
+        Alliance::FourteenEyes,
+    ];
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Alliance::EuropeanUnion => "eu",
+            Alliance::EuropeanEconomicArea => "eea",
+            Alliance::FiveEyes => "five-eyes",
+            Alliance::NineEyes => "nine-eyes",
+            Alliance::FourteenEyes => "fourteen-eyes",
+        }
+    }
+
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Alliance> {
+        Alliance::ALL
+            .into_iter()
+            .find(|alliance| alliance.as_str().eq_ignore_ascii_case(name))
+    }
+
+    #[must_use]
+    pub fn contains(self, country_code: &str) -> bool {
+        let members = match self {
+            Alliance::EuropeanUnion => &EUROPEAN_UNION[..],
+            Alliance::EuropeanEconomicArea => &EUROPEAN_ECONOMIC_AREA[..],
+            Alliance::FiveEyes => &FOURTEEN_EYES[..5],
+            Alliance::NineEyes => &FOURTEEN_EYES[..9],
+            Alliance::FourteenEyes => &FOURTEEN_EYES[..],
+        };
+        members
+            .iter()
+            .any(|member| member.eq_ignore_ascii_case(country_code))
+    }
+}
+
+const EUROPEAN_UNION: [&str; 27] = [
+    "AT", "BE", "BG", "CY", "CZ", "DE", "DK", "EE", "ES", "FI", "FR", "GR", "HR", "HU", "IE", "IT",
+    "LT", "LU", "LV", "MT", "NL", "PL", "PT", "RO", "SE", "SI", "SK",
+];
+const EUROPEAN_ECONOMIC_AREA: [&str; 30] = [
+    "AT", "BE", "BG", "CY", "CZ", "DE", "DK", "EE", "ES", "FI", "FR", "GR", "HR", "HU", "IE", "IT",
+    "LT", "LU", "LV", "MT", "NL", "PL", "PT", "RO", "SE", "SI", "SK", "IS", "LI", "NO",
+];
+// the five eyes come first, followed by the members joining for the nine and fourteen eyes
+const FOURTEEN_EYES: [&str; 14] = [
+    "AU", "CA", "GB", "NZ", "US", "DK", "FR", "NL", "NO", "BE", "DE", "ES", "IT", "SE",
+];
+
+/// continent and alliances of the country an instance is hosted in
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Location {
+    pub region: String,            // continent, empty if the country is unknown
+    pub jurisdiction: Vec<String>, // alliances the country belongs to
+}
+
+impl From<&str> for Location {
+    fn from(country_code: &str) -> Self {
+        Location {
+            region: Continent::of(country_code)
+                .map(Continent::as_str)
+                .unwrap_or_default()
+                .into(),
+            jurisdiction: Alliance::ALL
+                .into_iter()
+                .filter(|alliance| alliance.contains(country_code))
+                .map(|alliance| alliance.as_str().into())
+                .collect(),
+        }
+    }
+}
+
+// the location gets derived from the country code, selected by the database
+impl From<String> for Location {
+    fn from(country_code: String) -> Self {
+        Location::from(country_code.as_str())
+    }
+}
+
+/// whether the country belongs to the named continent or alliance, or, for the "unknown"
+/// region, to none of the continents - other unknown regions contain no countries
+#[must_use]
+pub fn in_region(country_code: &str, region: &str) -> bool {
+    if let Some(alliance) = Alliance::from_name(region) {
+        return alliance.contains(country_code);
+    }
+    if region.eq_ignore_ascii_case(UNKNOWN_REGION) {
+        return Continent::of(country_code).is_none();
+    }
+    Continent::from_name(region)
+        .is_some_and(|continent| Continent::of(country_code) == Some(continent))
+}
+
+#[test]
+fn test_continents() {
+    // every country is on exactly one continent
+    for country in CountryCode::iter() {
+        let continents: Vec<Continent> = Continent::ALL
+            .into_iter()
+            .filter(|continent| continent.countries().contains(&country.alpha2()))
+            .collect();
+        assert_eq!(continents.len(), 1, "continents of {}", country.alpha2());
+    }
+    assert_eq!(Continent::of("ch"), Some(Continent::Europe));
+    assert_eq!(Continent::of("XK"), None);
+    assert!(in_region("BR", "South-America"));
+    assert!(!in_region("BR", "atlantis"));
+    // instances that couldn't be located aren't filed under Antarctica
+    assert_eq!(Continent::of(UNKNOWN_COUNTRY), None);
+    assert_eq!(Continent::of("BV"), Some(Continent::Antarctica));
+    assert!(in_region(UNKNOWN_COUNTRY, "Unknown") && in_region("XK", "unknown"));
+    assert!(!in_region(UNKNOWN_COUNTRY, "antarctica") && !in_region("BR", "unknown"));
+    assert_eq!(Location::from(UNKNOWN_COUNTRY), Location::default());
+}
+
+#[test]
+fn test_alliances() {
+    assert!(in_region("DE", "EU") && in_region("DE", "eea"));
+    assert!(!in_region("CH", "eu") && !in_region("CH", "fourteen-eyes"));
+    assert!(in_region("NO", "eea") && !in_region("NO", "eu"));
+    assert!(in_region("NZ", "five-eyes") && in_region("NZ", "fourteen-eyes"));
+    assert!(in_region("FR", "nine-eyes") && !in_region("FR", "five-eyes"));
+    assert!(in_region("SE", "fourteen-eyes") && !in_region("SE", "nine-eyes"));
+    // all members are known countries and the EU is part of the EEA
+    for member in EUROPEAN_ECONOMIC_AREA.iter().chain(&FOURTEEN_EYES) {
+        assert!(
+            CountryCode::for_alpha2(member).is_ok(),
+            "unknown member {member}"
+        );
+    }
+    assert!(
+        EUROPEAN_UNION
+            .iter()
+            .all(|member| EUROPEAN_ECONOMIC_AREA.contains(member))
+    );
+    assert_eq!(
+        Location::from("DK"),
+        Location {
+            region: "europe".into(),
+            jurisdiction: vec![
+                "eu".into(),
+                "eea".into(),
+                "nine-eyes".into(),
+                "fourteen-eyes".into()
+            ],
+        }
+    );
+    assert_eq!(Location::from("XK"), Location::default());
+}
@@ -0,0 +1,189 @@
+// robots.txt parser, following RFC 9309: https://www.rfc-editor.org/rfc/rfc9309
+
+pub const PRODUCT_TOKEN: &str = "PrivateBinDirectoryBot";
+pub const MAX_SIZE: usize = 512_000; // parsing limit, the RFC requires at least 500 KiB
+
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+#[derive(Default)]
+struct Group {
+    user_agents: Vec<String>,
+    rules: Vec<Rule>,
+}
+
+pub struct RobotsTxt {
+    groups: Vec<Group>,
+}
+
+impl RobotsTxt {
+    #[must_use]
+    pub fn parse(content: &str) -> RobotsTxt {
+        let mut groups: Vec<Group> = vec![];
+        // consecutive user-agent lines share the rules that follow them
+        let mut in_user_agents = false;
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if !in_user_agents {
+                        groups.push(Group::default());
+                        in_user_agents = true;
+                    }
+                    if let Some(group) = groups.last_mut() {
+                        group.user_agents.push(Self::token_of(value));
+                    }
+                }
+                key @ ("allow" | "disallow") => {
+                    in_user_agents = false;
+                    // rules outside of a group and empty rules don't apply to anything
+                    if let Some(group) = groups.last_mut()
+                        && !value.is_empty()
+                    {
+                        group.rules.push(Rule {
+                            allow: key == "allow",
+                            pattern: value.into(),
+                        });
+                    }
+                }
+                // other records, like sitemaps, neither start nor end a group
+                _ => {}
+            }
+        }
+        RobotsTxt { groups }
+    }
+
+    /// whether the crawler with the given product token may access the path, rules of all
+    /// groups matching it get combined, the groups for all crawlers only apply as a fallback
+    #[must_use]
+    pub fn is_allowed(&self, product_token: &str, path: &str) -> bool {
+        let product_token = product_token.to_ascii_lowercase();
+        let mut groups: Vec<&Group> = self
+            .groups
+            .iter()
+            .filter(|group| group.user_agents.contains(&product_token))
+            .collect();
+        if groups.is_empty() {
+            groups = self
+                .groups
+                .iter()
+                .filter(|group| group.user_agents.iter().any(|agent| agent == "*"))
+                .collect();
+        }
+        // the most specific, that is longest, match wins, allow rules win ties
+        groups
+            .into_iter()
+            .flat_map(|group| &group.rules)
+            .filter(|rule| Self::matches(&rule.pattern, path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .is_none_or(|rule| rule.allow)
+    }
+
+    // the product token is the leading part of the user-agent, i.e. without a version
+    fn token_of(user_agent: &str) -> String {
+        if user_agent.starts_with('*') {
+            return "*".into();
+        }
+        user_agent
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+            .collect::<String>()
+            .to_ascii_lowercase()
+    }
+
+    // patterns match path prefixes, "*" matches any sequence of characters and a trailing "$"
+    // anchors the pattern to the end of the path
+    fn matches(pattern: &str, path: &str) -> bool {
+        let (pattern, anchored) = match pattern.strip_suffix('$') {
+            Some(pattern) => (pattern, true),
+            None => (pattern, false),
+        };
+        let parts: Vec<&str> = pattern.split('*').collect();
+        let Some(mut rest) = path.strip_prefix(parts[0]) else {
+            return false;
+        };
+        let last = parts.len() - 1;
+        if last == 0 {
+            return !anchored || rest.is_empty();
+        }
+        for part in &parts[1..last] {
+            match rest.find(part) {
+                Some(position) => rest = &rest[position + part.len()..],
+                None => return false,
+            }
+        }
+        if anchored {
+            rest.ends_with(parts[last])
+        } else {
+            rest.contains(parts[last])
+        }
+    }
+}
+
+#[test]
+fn test_groups() {
+    let robots = RobotsTxt::parse(
+        "# comments and unknown records are ignored\n\
+        Sitemap: https://example.com/sitemap.xml\n\
+        User-agent: *\n\
+        Disallow: /\n\
+        \n\
+        user-agent: Googlebot\n\
+        USER-AGENT: privatebindirectorybot/1.0 # version is ignored\n\
+        disallow: /private\n\
+        Crawl-delay: 10\n\
+        Allow: /private/paste\n\
+        \n\
+        User-agent: OtherBot\n\
+        Disallow: /paste\n",
+    );
+    assert!(robots.is_allowed(PRODUCT_TOKEN, "/"));
+    assert!(robots.is_allowed(PRODUCT_TOKEN, "/paste/"));
+    assert!(!robots.is_allowed(PRODUCT_TOKEN, "/private/"));
+    assert!(robots.is_allowed(PRODUCT_TOKEN, "/private/paste/"));
+    assert!(!robots.is_allowed("UnknownBot", "/paste/"));
+    assert!(!robots.is_allowed("OtherBot", "/paste/"));
+}
+
+#[test]
+fn test_wildcard_fallback() {
+    let robots = RobotsTxt::parse("User-agent: *\nDisallow: /paste/\n");
+    assert!(robots.is_allowed(PRODUCT_TOKEN, "/"));
+    assert!(!robots.is_allowed(PRODUCT_TOKEN, "/paste/"));
+
+    // an empty group for the crawler overrides the fallback
+    let robots = RobotsTxt::parse(
+        "User-agent: *\nDisallow: /\n\nUser-agent: PrivateBinDirectoryBot\nDisallow:\n",
+    );
+    assert!(robots.is_allowed(PRODUCT_TOKEN, "/"));
+    assert!(!robots.is_allowed("UnknownBot", "/"));
+
+    assert!(RobotsTxt::parse("").is_allowed(PRODUCT_TOKEN, "/"));
+}
+
+#[test]
+fn test_patterns() {
+    let robots = RobotsTxt::parse(
+        "User-agent: PrivateBinDirectoryBot\n\
+        Disallow: /*.php$\n\
+        Allow: /index.php$\n\
+        Disallow: /bin/*/old\n\
+        Allow: /bin/\n\
+        Disallow: /bin\n",
+    );
+    assert!(!robots.is_allowed(PRODUCT_TOKEN, "/paste.php"));
+    assert!(robots.is_allowed(PRODUCT_TOKEN, "/paste.php?query"));
+    assert!(robots.is_allowed(PRODUCT_TOKEN, "/index.php"));
+    assert!(!robots.is_allowed(PRODUCT_TOKEN, "/bin/v1/old/"));
+    assert!(robots.is_allowed(PRODUCT_TOKEN, "/bin/v1/"));
+    assert!(!robots.is_allowed(PRODUCT_TOKEN, "/binary"));
+    // equally specific rules, allow wins
+    let robots = RobotsTxt::parse("User-agent: *\nDisallow: /paste\nAllow: /paste\n");
+    assert!(robots.is_allowed(PRODUCT_TOKEN, "/paste"));
+}
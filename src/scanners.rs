@@ -0,0 +1,455 @@
+use super::connections::{request_post, throttle, timeout_seconds};
+use super::functions::get_epoch;
+use super::models::ScanNew;
+use futures::future::BoxFuture;
+use http_body_util::BodyExt; // BodyExt provides the Iterator trait
+use hyper::StatusCode;
+use hyper::body::{Body, Buf}; // Body provides the size_hint() trait, Buf provides the chunk() trait
+use rand::prelude::*;
+use rocket::serde::{Deserialize, Serialize, json};
+use rocket::warn;
+use rustls::client::WebPkiServerVerifier;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{
+    ClientConfig, DigitallySignedStruct, ProtocolVersion, RootCertStore, SignatureScheme,
+};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::net::TcpStream;
+use tokio::time::{Duration, sleep, timeout};
+use tokio_rustls::TlsConnector;
+use url::Url;
+use x509_parser::prelude::{FromDer, X509Certificate};
+use x509_parser::public_key::PublicKey;
+
+const OBSERVATORY_API: &str = "https://observatory-api.mdn.mozilla.net/api/v2/scan?host=";
+const OBSERVATORY_MAX_CONTENT_LENGTH: u64 = 10240;
+
+// as configured, set once at startup, or by the mock server of the tests
+static OBSERVATORY_API_CONFIGURED: OnceLock<String> = OnceLock::new();
+static TLS_VERIFIER: OnceLock<Arc<WebPkiServerVerifier>> = OnceLock::new();
+
+// all scanners run on each instance, results are stored in the scans table
+pub static SCANNERS: &[&dyn Scanner] = &[&MozillaObservatory, &TlsConfiguration];
+
+pub trait Scanner: Sync {
+    /// name of the scanner, as stored in the scanner column of the scans table
+    fn name(&self) -> &'static str;
+
+    /// rate the instance at the given URL, returns a rating of "-" if the scan failed
+    fn scan<'a>(&'a self, url: &'a str) -> BoxFuture<'a, ScanNew>;
+
+    /// whether a failed scan may still be in progress on the side of the scanner, so it is worth
+    /// waiting for it to conclude and scanning again
+    fn retry_pending(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct ObservatoryScan<'r> {
+    error: Option<&'r str>,
+    grade: Option<&'r str>,
+    status_code: Option<u16>,
+}
+
+pub struct MozillaObservatory;
+
+/// point the observatory scanner at a different API, the host to scan gets appended to the
+/// given URL - only the first call takes effect, the public API is used if there is none
+pub fn set_observatory_api(api: String) {
+    let _ = OBSERVATORY_API_CONFIGURED.set(api);
+}
+
+impl Scanner for MozillaObservatory {
+    fn name(&self) -> &'static str {
+        "mozilla_observatory"
+    }
+
+    fn scan<'a>(&'a self, url: &'a str) -> BoxFuture<'a, ScanNew> {
+        Box::pin(self.check_rating(url))
+    }
+
+    // the observatory API may not have concluded its scan yet
+    fn retry_pending(&self) -> bool {
+        true
+    }
+}
+
+impl MozillaObservatory {
+    /// check rating at mozilla observatory
+    ///
+    /// # Panics
+    ///
+    /// May panic in `res.collect().await.unwrap()`.
+    async fn check_rating(&self, url: &str) -> ScanNew {
+        if let Ok(parsed_url) = Url::parse(url)
+            && let Some(host) = parsed_url.host_str()
+        {
+            let observatory_api = OBSERVATORY_API_CONFIGURED
+                .get()
+                .map_or(OBSERVATORY_API, String::as_str);
+            let observatory_url = format!("{observatory_api}{host}");
+            for _retries in 0..5 {
+                // pause before scanning, to spread the load during full syncs
+                let backoff_ms = rand::rng().random_range(500..3000);
+                sleep(Duration::from_millis(backoff_ms)).await;
+                match request_post(&observatory_url).await {
+                    Ok(res) => {
+                        if res.status() == StatusCode::OK {
+                            let response_content_length = res
+                                .body()
+                                .size_hint()
+                                .upper()
+                                .unwrap_or(OBSERVATORY_MAX_CONTENT_LENGTH);
+                            // protect from malicious response
+                            if response_content_length >= OBSERVATORY_MAX_CONTENT_LENGTH {
+                                warn!(
+                                    "Failed retrieving observatory rating for {url} due response being too large (>= {OBSERVATORY_MAX_CONTENT_LENGTH})."
+                                );
+                                break;
+                            }
+                            let body_bytes = res.collect().await.unwrap().aggregate();
+                            if let Ok(api_response) =
+                                json::from_slice::<ObservatoryScan>(body_bytes.chunk())
+                            {
+                                if api_response.error.is_none()
+                                    && let Some(grade) = api_response.grade
+                                    && api_response
+                                        .status_code
+                                        .unwrap_or(StatusCode::EXPECTATION_FAILED.as_u16())
+                                        == StatusCode::OK.as_u16()
+                                {
+                                    return ScanNew::new(self.name(), grade, 0);
+                                }
+                                // initiate a rescan, if the error indicates a timeout
+                                // see: https://github.com/mdn/mdn-http-observatory/blob/main/src/api/errors.js
+                                let error = api_response.error.unwrap_or("");
+                                if error == "error-unknown" {
+                                    let backoff_ms = rand::rng().random_range(2000..5000);
+                                    sleep(Duration::from_millis(backoff_ms)).await;
+                                    continue;
+                                }
+                                warn!(
+                                    "Failed retrieving observatory rating for {url} due to {error}."
+                                );
+                            } else {
+                                warn!(
+                                    "Failed retrieving observatory rating for {url} due JSON decoding issue."
+                                );
+                            }
+                        } else {
+                            let status = res.status();
+                            if status == StatusCode::INTERNAL_SERVER_ERROR {
+                                let backoff_ms = rand::rng().random_range(2000..5000);
+                                sleep(Duration::from_millis(backoff_ms)).await;
+                                continue;
+                            }
+                            let status = res.status().as_u16();
+                            warn!(
+                                "Failed retrieving observatory rating for {url} due to HTTP status {status}."
+                            );
+                        }
+                    }
+                    _ => {
+                        warn!(
+                            "Failed retrieving observatory rating for {url} due request failing or timeout."
+                        );
+                    }
+                }
+                break;
+            }
+        }
+        ScanNew::new(self.name(), "-", 0)
+    }
+}
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct TlsDetails {
+    pub not_after: i64, // UNIX timestamp
+    pub issuer: String,
+    pub key_type: String,
+    pub protocol: String,
+    pub cipher_suite: String,
+    pub error: String,
+}
+
+impl TlsDetails {
+    // expiry, issuer and key of the certificate presented by the instance
+    fn read_certificate(&mut self, certificate: &CertificateDer<'_>) {
+        let Ok((_, x509)) = X509Certificate::from_der(certificate) else {
+            return;
+        };
+        self.not_after = x509.validity().not_after.timestamp();
+        self.issuer = x509.issuer().to_string();
+        self.key_type = match x509.public_key().parsed() {
+            Ok(PublicKey::RSA(key)) => format!("RSA {}", key.key_size()),
+            Ok(PublicKey::EC(key)) => format!("EC {}", key.key_size()),
+            Ok(_) => "other".into(),
+            Err(_) => "unknown".into(),
+        };
+    }
+
+    // days until the certificate expires, negative if it already did
+    #[must_use]
+    pub fn cert_days(scans: &[ScanNew]) -> Option<i32> {
+        let scan = scans
+            .iter()
+            .find(|scan| scan.scanner == TlsConfiguration.name())?;
+        let details = json::from_str::<TlsDetails>(&scan.details).ok()?;
+        if details.not_after == 0 {
+            return None;
+        }
+        let now = i64::try_from(get_epoch()).unwrap_or(i64::MAX);
+        i32::try_from((details.not_after - now) / 86400).ok()
+    }
+}
+
+// verifies the certificate against the webpki roots and records it, whether it passes or not
+#[derive(Debug)]
+struct RecordingVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    presented: Mutex<Option<CertificateDer<'static>>>,
+}
+
+impl RecordingVerifier {
+    fn presented(&self) -> Option<CertificateDer<'static>> {
+        self.presented.lock().ok()?.clone()
+    }
+}
+
+impl ServerCertVerifier for RecordingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Ok(mut presented) = self.presented.lock() {
+            *presented = Some(end_entity.clone().into_owned());
+        }
+        self.inner
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+pub struct TlsConfiguration;
+
+impl Scanner for TlsConfiguration {
+    fn name(&self) -> &'static str {
+        "tls"
+    }
+
+    fn scan<'a>(&'a self, url: &'a str) -> BoxFuture<'a, ScanNew> {
+        Box::pin(self.check_tls(url))
+    }
+}
+
+impl TlsConfiguration {
+    // connect to the instance and inspect the negotiated TLS session and certificate
+    async fn check_tls(&self, url: &str) -> ScanNew {
+        let mut scan = ScanNew::new(self.name(), "-", 0);
+        let Ok(parsed_url) = Url::parse(url) else {
+            return scan;
+        };
+        if parsed_url.scheme() != "https" {
+            return scan;
+        }
+        let (Some(host), Some(port)) = (parsed_url.host_str(), parsed_url.port_or_known_default())
+        else {
+            return scan;
+        };
+        let Ok(server_name) = ServerName::try_from(host.to_owned()) else {
+            return scan;
+        };
+
+        // the verifier keeps the presented certificate, to report its expiry even if it fails
+        let verifier = Arc::new(RecordingVerifier {
+            inner: TLS_VERIFIER
+                .get_or_init(|| {
+                    let root_store = RootCertStore {
+                        roots: webpki_roots::TLS_SERVER_ROOTS.into(),
+                    };
+                    WebPkiServerVerifier::builder(Arc::new(root_store))
+                        .build()
+                        .expect("verifier using the webpki roots")
+                })
+                .clone(),
+            presented: Mutex::new(None),
+        });
+        let connector = TlsConnector::from(Arc::new(
+            ClientConfig::builder()
+                .dangerous()
+                .with_custom_certificate_verifier(verifier.clone())
+                .with_no_client_auth(),
+        ));
+        throttle(host).await;
+        let handshake = async {
+            let stream = TcpStream::connect((host, port)).await?;
+            connector.connect(server_name, stream).await
+        };
+        let mut details = TlsDetails::default();
+        match timeout(Duration::from_secs(timeout_seconds()), handshake).await {
+            Ok(Ok(stream)) => {
+                let (_, connection) = stream.get_ref();
+                details.protocol = match connection.protocol_version() {
+                    Some(ProtocolVersion::TLSv1_3) => "TLSv1.3".into(),
+                    Some(ProtocolVersion::TLSv1_2) => "TLSv1.2".into(),
+                    Some(version) => format!("{version:?}"),
+                    None => String::new(),
+                };
+                if let Some(cipher_suite) = connection.negotiated_cipher_suite() {
+                    details.cipher_suite = format!("{:?}", cipher_suite.suite());
+                }
+                // TLS 1.3 removed obsolete ciphers and offers forward secrecy by default
+                scan = ScanNew::new(
+                    self.name(),
+                    if details.protocol == "TLSv1.3" {
+                        "A"
+                    } else {
+                        "B"
+                    },
+                    0,
+                );
+            }
+            // only failures to negotiate TLS or verify the certificate are rated, an unreachable
+            // host may just be temporarily down
+            Ok(Err(e))
+                if e.get_ref()
+                    .is_some_and(<dyn std::error::Error + Send + Sync>::is::<rustls::Error>) =>
+            {
+                details.error = e.to_string();
+                scan = ScanNew::new(self.name(), "F", 0);
+            }
+            Ok(Err(e)) => {
+                warn!("Failed scanning TLS configuration of {url} due to: {e}");
+                return scan;
+            }
+            Err(_) => {
+                warn!("Failed scanning TLS configuration of {url} due to timeout.");
+                return scan;
+            }
+        }
+        if let Some(certificate) = verifier.presented() {
+            details.read_certificate(&certificate);
+        }
+        if let Ok(encoded_details) = json::to_string(&details) {
+            scan.details = encoded_details;
+        }
+        scan
+    }
+}
+
+#[tokio::test]
+#[ignore = "requires network access"]
+async fn test_tls() {
+    let scans = vec![TlsConfiguration.scan("https://privatebin.net").await];
+    assert_eq!(scans[0].rating, "A");
+    assert!(TlsDetails::cert_days(&scans).is_some_and(|cert_days| cert_days > 0));
+}
+
+#[tokio::test]
+async fn test_tls_handshake_failure() {
+    use super::test_support::{Fixture, serve};
+
+    // the mock server only speaks plain HTTP
+    let url = serve("/tls", Fixture::privatebin("2.0.1")).replacen("http://", "https://", 1);
+    let scans = vec![TlsConfiguration.scan(&url).await];
+    assert_eq!(scans[0].rating, "F");
+    assert_eq!(TlsDetails::cert_days(&scans), None);
+}
+
+#[tokio::test]
+async fn test_tls_connection_refused() {
+    // reserve a free port and release it again, so connecting to it gets refused
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("free port")
+        .port();
+    let scan = TlsConfiguration
+        .scan(&format!("https://127.0.0.1:{port}/"))
+        .await;
+    assert_eq!(scan.rating, "-");
+    assert_eq!(scan.details, "{}");
+}
+
+#[tokio::test]
+async fn test_tls_expired_certificate() {
+    use rustls::ServerConfig;
+    use rustls::pki_types::PrivatePkcs8KeyDer;
+    use tokio_rustls::TlsAcceptor;
+
+    // self-signed certificate, that expired on 2020-02-01
+    let certificate =
+        CertificateDer::from(include_bytes!("../tests/fixtures/expired-certificate.der").to_vec());
+    let key =
+        PrivatePkcs8KeyDer::from(include_bytes!("../tests/fixtures/expired-key.der").to_vec());
+    let acceptor = TlsAcceptor::from(Arc::new(
+        ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(vec![certificate], key.into())
+            .expect("server configuration with the expired certificate"),
+    ));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("binding TLS server to a port");
+    let address = listener.local_addr().expect("address of TLS server");
+    tokio::spawn(async move {
+        if let Ok((stream, _)) = listener.accept().await {
+            let _ = acceptor.accept(stream).await;
+        }
+    });
+
+    let scans = vec![TlsConfiguration.scan(&format!("https://{address}")).await];
+    assert_eq!(scans[0].rating, "F");
+    let details: TlsDetails = json::from_str(&scans[0].details).expect("TLS details");
+    assert_eq!(details.not_after, 1_580_515_200);
+    assert!(details.issuer.contains("Expired Test CA"));
+    assert!(!details.error.is_empty());
+    assert!(TlsDetails::cert_days(&scans).is_some_and(|cert_days| cert_days < -2000));
+}
+
+#[tokio::test]
+async fn test_observatory() {
+    use super::test_support::{Fixture, serve};
+
+    let url = serve("/observatory", Fixture::privatebin("2.0.1"));
+    let scan = MozillaObservatory.scan(&url).await;
+    assert_eq!(scan.rating, "A+");
+    assert!(MozillaObservatory.retry_pending());
+}
+
+#[tokio::test]
+async fn test_tls_without_https() {
+    let scans = vec![TlsConfiguration.scan("http://privatebin.net").await];
+    assert_eq!(scans[0].rating, "-");
+    assert_eq!(TlsDetails::cert_days(&scans), None);
+    // not applicable to plain HTTP, so there is no use in scanning again
+    assert!(!TlsConfiguration.retry_pending());
+}
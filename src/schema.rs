@@ -1,9 +1,50 @@
+table! {
+    audit_log (id) {
+        id -> Integer,
+        created -> Timestamp,
+        action -> Text,
+        target -> Text,
+        details -> Text,
+    }
+}
+
+table! {
+    blocklist (id) {
+        id -> Integer,
+        domain -> Text,
+        reason -> Text,
+        created -> Timestamp,
+    }
+}
+
 table! {
     checks (id) {
         id -> Integer,
         updated -> Timestamp,
         up -> Bool,
         instance_id -> Integer,
+        latency -> Integer,
+        status -> Integer,
+    }
+}
+
+table! {
+    headers (id) {
+        id -> Integer,
+        name -> Text,
+        passed -> Bool,
+        instance_id -> Integer,
+    }
+}
+
+table! {
+    instance_events (id) {
+        id -> Integer,
+        updated -> Timestamp,
+        field -> Text,
+        old_value -> Text,
+        new_value -> Text,
+        instance_id -> Integer,
     }
 }
 
@@ -17,6 +58,41 @@ table! {
         country_id -> Text,
         attachments -> Bool,
         csp_header -> Bool,
+        discussion -> Bool,
+        password -> Bool,
+        burn_after_reading -> Bool,
+        qr_code -> Bool,
+        expiration_options -> Text,
+        expiration_default -> Text,
+        formatters -> Text,
+        formatter_default -> Text,
+        languages -> Text,
+        template -> Text,
+        status -> Text,
+        status_changed -> Nullable<Timestamp>,
+        failures -> Integer,
+        verified -> Bool,
+        delisted -> Bool,
+        display_name -> Text,
+        description -> Text,
+        contact -> Text,
+        pinned -> Bool,
+        hidden -> Bool,
+        notes -> Text,
+        csp_policy -> Text,
+    }
+}
+
+table! {
+    scan_runs (id) {
+        id -> Integer,
+        started -> Timestamp,
+        finished -> Nullable<Timestamp>,
+        checked -> Integer,
+        updated -> Integer,
+        removed -> Integer,
+        failed -> Integer,
+        suspended -> Integer,
     }
 }
 
@@ -27,10 +103,35 @@ table! {
         rating -> Text,
         percent -> Integer,
         instance_id -> Integer,
+        details -> Text,
+    }
+}
+
+table! {
+    uptimes (id) {
+        id -> Integer,
+        resolution -> Text,
+        period -> Timestamp,
+        checks -> Integer,
+        up -> Integer,
+        instance_id -> Integer,
     }
 }
 
 joinable!(checks -> instances (instance_id));
+joinable!(headers -> instances (instance_id));
+joinable!(instance_events -> instances (instance_id));
 joinable!(scans -> instances (instance_id));
+joinable!(uptimes -> instances (instance_id));
 
-allow_tables_to_appear_in_same_query!(checks, instances, scans,);
+allow_tables_to_appear_in_same_query!(
+    audit_log,
+    blocklist,
+    checks,
+    headers,
+    instance_events,
+    instances,
+    scan_runs,
+    scans,
+    uptimes,
+);
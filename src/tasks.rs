@@ -1,171 +1,482 @@
-use super::models::{CheckNew, Instance, InstanceNew, PrivateBin, ScanNew};
+use super::functions::{get_monitored_instance, get_monitored_instances, set_instance_status};
+use super::models::{
+    CheckNew, DirectoryConfig, DirectoryError, HeaderChecks, HeaderNew, Instance, InstanceEventNew,
+    InstanceNew, InstanceStatus, PrivateBin, ScanNew, UptimeResolution,
+};
+use super::scanners::SCANNERS;
 use super::schema::instances::dsl::{
-    attachments, country_id, csp_header, https, https_redirect, id, instances, version,
+    attachments, country_id, csp_header, csp_policy, https, https_redirect, id, instances, version,
 };
-use super::{Build, Rocket, get_epoch, get_instances};
+use super::{DirectoryDbConn, get_epoch};
 use diesel::{
     SqliteConnection, delete,
     dsl::{sql, sql_query},
     insert_into,
     prelude::*,
-    update,
+    replace_into, update,
 };
-use futures::future::select_all;
-use rocket_sync_db_pools::Config;
+use futures::stream::{self, StreamExt};
+use rand::prelude::*;
+use rocket::fairing::AdHoc;
+use rocket_sync_db_pools::ConnectionPool;
 use std::fmt::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
 use std::time::{Duration, Instant};
-use tokio::time::sleep;
+use tokio::time::{MissedTickBehavior, interval_at, sleep};
 
+// defaults, unless configured otherwise in the directory section of the Rocket configuration
 pub const CRON_INTERVAL: u64 = 900; // 15 minutes
 pub const CHECKS_TO_STORE: u64 = 100; // amount of checks to keep
-pub const MAX_FAILURES: u64 = 90; // remove instances that failed this many times
+pub const MAX_FAILURES: u64 = 90; // suspend instances that failed this many times in a row
+pub const DEGRADED_AFTER: u64 = 4; // consecutive failures, before an instance is degraded
+pub const SUSPENSION_DAYS: u64 = 7; // days a suspended instance is kept, before its removal
+pub const TOMBSTONE_DAYS: u64 = 30; // days a removed instance is kept, before it gets purged
+pub const UPTIMES_TO_STORE: u64 = 366; // days of aggregated uptimes to keep
+pub const FULL_CHECK_INTERVAL: u64 = 86400; // 1 day
+pub const SCHEDULER_JITTER: u64 = 60; // maximum seconds to delay scheduled checks by
+pub const MAX_CONCURRENT_CHECKS: usize = 32; // instances to check at the same time
 
-struct InstanceCheckResult<'a> {
+struct InstanceCheckResult {
     message: String,
-    scan_update: Option<ScanNew>,
-    scan_update_success: String,
-    instance: &'a Instance,
+    error: Option<DirectoryError>,
+    scan_updates: Vec<(ScanNew, String)>,
+    header_updates: Vec<HeaderNew>,
+    events: Vec<InstanceEventNew>,
+    instance_id: i32,
+    instance_url: String,
+    instance_status: Option<InstanceStatus>, // none if the stored status is unknown
     instance_update: Option<InstanceNew>,
     instance_update_success: String,
 }
 
-/// # Panics
-///
-/// May panic in `Config::from`.
-#[allow(clippy::too_many_lines)]
-pub async fn check_full(rocket: Rocket<Build>) {
-    use super::schema::scans::dsl::{instance_id, percent, rating, scanner, scans};
-
-    let directory_config =
-        Config::from("directory", &rocket).expect("configuration of directory database");
-    let mut conn = SqliteConnection::establish(&directory_config.url)
-        .expect("connection to directory database");
-    sql_query("PRAGMA foreign_keys = ON")
-        .execute(&mut conn)
-        .expect("enable foreign key constraints");
-    let cached_instances = get_instances().load::<Instance>(&mut conn);
-    match cached_instances {
-        Ok(instance_list) => {
-            let mut instance_update_queries = vec![];
-            let mut scan_update_queries = vec![];
-            let mut children = vec![];
-            for instance in &instance_list {
-                children.push(check_instance(instance));
+struct StatusUpdate {
+    instance_id: i32,
+    failures: i32,
+    old_status: InstanceStatus,
+    new_status: InstanceStatus,
+}
+
+#[derive(Clone, Copy)]
+enum ScheduledCheck {
+    Up,
+    Full,
+}
+
+/// fairing that runs the uptime and full checks periodically alongside the web service, if the
+/// scheduler is enabled in the configuration
+#[must_use]
+pub fn scheduler() -> AdHoc {
+    AdHoc::on_liftoff("Check Scheduler", |rocket| {
+        Box::pin(async move {
+            let config = rocket
+                .state::<DirectoryConfig>()
+                .cloned()
+                .unwrap_or_default();
+            if !config.scheduler {
+                return;
             }
-            let mut pinned_children: Vec<_> = children.into_iter().map(Box::pin).collect();
-            while !pinned_children.is_empty() {
-                let (result, _index, remaining_children) = select_all(pinned_children).await;
-                pinned_children = remaining_children;
-                let message = result.message;
-                print!("{message}");
-
-                // robots.txt must have changed or site no longer an instance, delete it immediately
-                if message.ends_with("doesn't want to get added to the directory.\"\n")
-                    || message.ends_with("doesn't seem to be a PrivateBin instance.\"\n")
-                {
-                    match delete(instances.filter(id.eq(result.instance.id))).execute(&mut conn) {
-                        Ok(_) => print!("    removed the instance, due to: {message}"),
-                        Err(e) => {
-                            println!("    error removing the instance: {e:?}");
-                        }
-                    }
-                    continue;
-                }
+            let Some(pool) = DirectoryDbConn::pool(rocket).cloned() else {
+                println!("scheduler not started, the database pool is unavailable");
+                return;
+            };
+            let config = Arc::new(config);
+            for check in [ScheduledCheck::Up, ScheduledCheck::Full] {
+                tokio::spawn(schedule(
+                    check,
+                    pool.clone(),
+                    config.clone(),
+                    rocket.shutdown(),
+                ));
+            }
+        })
+    })
+}
 
-                if let Some(updated_scan) = result.scan_update {
-                    scan_update_queries.push((
-                        update(
-                            scans
-                                .filter(instance_id.eq(result.instance.id))
-                                .filter(scanner.eq(updated_scan.scanner)),
-                        )
-                        .set((
-                            rating.eq(updated_scan.rating.clone()),
-                            percent.eq(updated_scan.percent),
-                        )),
-                        result.scan_update_success,
-                        result.instance.url.clone(),
-                    ));
-                }
-                if let Some(updated_instance) = result.instance_update {
-                    instance_update_queries.push((
-                        update(instances.filter(id.eq(result.instance.id))).set((
-                            version.eq(updated_instance.version),
-                            https.eq(updated_instance.https),
-                            https_redirect.eq(updated_instance.https_redirect),
-                            csp_header.eq(updated_instance.csp_header),
-                            attachments.eq(updated_instance.attachments),
-                            country_id.eq(updated_instance.country_id),
-                        )),
-                        result.instance_update_success,
-                        result.instance.url.clone(),
-                    ));
-                }
+async fn schedule(
+    check: ScheduledCheck,
+    pool: ConnectionPool<DirectoryDbConn, SqliteConnection>,
+    config: Arc<DirectoryConfig>,
+    shutdown: rocket::Shutdown,
+) {
+    let (label, seconds) = match check {
+        ScheduledCheck::Up => ("uptime check", config.cron_interval),
+        ScheduledCheck::Full => ("full check", config.full_check_interval),
+    };
+    let period = Duration::from_secs(seconds);
+    let mut ticks = interval_at(tokio::time::Instant::now() + period, period);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let running = Arc::new(AtomicBool::new(false));
+    loop {
+        tokio::select! {
+            _ = ticks.tick() => {}
+            () = shutdown.clone() => break,
+        }
+        // overlap protection, a slow run causes the following ones to be skipped
+        if running.swap(true, SeqCst) {
+            println!("skipped scheduled {label}, the previous one is still running");
+            continue;
+        }
+        let (pool, config, running) = (pool.clone(), config.clone(), running.clone());
+        tokio::spawn(async move {
+            // spread the load, in case multiple directories share the same schedule
+            let jitter = rand::rng().random_range(0..=config.scheduler_jitter);
+            sleep(Duration::from_secs(jitter)).await;
+            match DirectoryDbConn::from_pool(&pool).await {
+                Some(db) => match check {
+                    ScheduledCheck::Up => check_up(&db, &config).await,
+                    ScheduledCheck::Full => check_full(&db, &config).await,
+                },
+                None => println!("skipped scheduled {label}, no database connection available"),
             }
+            running.store(false, SeqCst);
+        });
+    }
+}
 
-            let timer = Instant::now();
-            for (query, query_success, instance_url) in instance_update_queries {
-                match query.execute(&mut conn) {
-                    Ok(_) => {
-                        print!("{query_success}");
-                    }
-                    Err(e) => {
-                        println!("Instance {instance_url} failed to be updated with error: {e:?}");
-                    }
-                }
+/// re-evaluate all properties of the instances and store the changes of each instance as soon
+/// as it got checked
+pub async fn check_full(db: &DirectoryDbConn, config: &DirectoryConfig) {
+    use super::schema::scan_runs::dsl::{
+        checked, failed, finished, id as run_id, removed, scan_runs, suspended, updated,
+    };
+
+    let scan_run = db
+        .run(|conn| {
+            insert_into(scan_runs).default_values().execute(conn)?;
+            diesel::select(sql::<diesel::sql_types::Integer>("last_insert_rowid()"))
+                .get_result::<i32>(conn)
+        })
+        .await;
+    let scan_run = match scan_run {
+        Ok(scan_run) => scan_run,
+        Err(e) => {
+            println!("failed to record the start of the full check with error: {e:?}");
+            return;
+        }
+    };
+
+    let cached_instances = db
+        .run(|conn| get_monitored_instances().load::<Instance>(conn))
+        .await;
+    match cached_instances {
+        Ok(instance_list) => {
+            let mut counts = ScanRunCounts::default();
+            let mut results = stream::iter(&instance_list)
+                .map(check_instance)
+                .buffer_unordered(config.max_concurrent_checks);
+            while let Some(result) = results.next().await {
+                print!("{}", result.message);
+                // record the progress, so it is visible while the full check is ongoing
+                counts = db
+                    .run(move |conn| {
+                        let mut counts = counts;
+                        counts.record(&store_instance_check(conn, result));
+                        if let Err(e) = update(scan_runs.filter(run_id.eq(scan_run)))
+                            .set((
+                                checked.eq(counts.checked),
+                                updated.eq(counts.updated),
+                                suspended.eq(counts.suspended),
+                                removed.eq(counts.removed),
+                                failed.eq(counts.failed),
+                            ))
+                            .execute(conn)
+                        {
+                            println!(
+                                "failed to record the progress of the full check with error: {e:?}"
+                            );
+                        }
+                        counts
+                    })
+                    .await;
             }
+
+            let config = config.clone();
+            db.run(move |conn| expire_instances(conn, &config)).await;
             println!(
-                "all instance update queries concluded ({:?})",
-                timer.elapsed()
+                "full check concluded, {} instances checked, {} updated, {} suspended, {} removed \
+                and {} failed",
+                counts.checked, counts.updated, counts.suspended, counts.removed, counts.failed
             );
+        }
+        Err(e) => {
+            println!("failed retrieving instances from database with error: {e:?}");
+        }
+    }
 
-            let timer = Instant::now();
-            for (query, query_success, instance_url) in scan_update_queries {
-                match query.execute(&mut conn) {
-                    Ok(_) => {
-                        print!("{query_success}");
-                    }
-                    Err(e) => {
-                        println!("Instance {instance_url} failed to be updated with error: {e:?}");
-                    }
-                }
+    if let Err(e) = db
+        .run(move |conn| {
+            update(scan_runs.filter(run_id.eq(scan_run)))
+                .set(finished.eq(diesel::dsl::now.nullable()))
+                .execute(conn)
+        })
+        .await
+    {
+        println!("failed to record the end of the full check with error: {e:?}");
+    }
+}
+
+/// re-evaluate all properties of a single instance right away, i.e. on request of a moderator,
+/// returns a summary of the outcome or nothing if the instance is unknown
+pub async fn rescan_instance(db: &DirectoryDbConn, instance_id: i32) -> Option<String> {
+    let instance = db
+        .run(move |conn| get_monitored_instance(instance_id).get_result::<Instance>(conn))
+        .await
+        .ok()?;
+    let result = check_instance(&instance).await;
+    let error = result.error.clone();
+    let outcome = db.run(move |conn| store_instance_check(conn, result)).await;
+    let instance_url = instance.url;
+    Some(match (error, outcome) {
+        (Some(e), CheckOutcome::Suspended) => {
+            format!("Instance {instance_url} got taken off the list, due to: {e}")
+        }
+        (Some(e), CheckOutcome::Removed) => {
+            format!("Instance {instance_url} got removed from the directory, due to: {e}")
+        }
+        (Some(e), _) => format!("Rescanning instance {instance_url} failed, due to: {e}"),
+        (None, CheckOutcome::Unchanged) => {
+            format!("Instance {instance_url} rescanned, no changes found.")
+        }
+        (None, CheckOutcome::Updated) => {
+            format!("Instance {instance_url} rescanned, changes got stored.")
+        }
+        (None, _) => format!("Instance {instance_url} rescanned, but storing the results failed."),
+    })
+}
+
+enum CheckOutcome {
+    Unchanged,
+    Updated,
+    Suspended,
+    Removed,
+    Failed,
+}
+
+#[derive(Clone, Copy, Default)]
+struct ScanRunCounts {
+    checked: i32,
+    updated: i32,
+    suspended: i32,
+    removed: i32,
+    failed: i32,
+}
+
+impl ScanRunCounts {
+    fn record(&mut self, outcome: &CheckOutcome) {
+        self.checked += 1;
+        match outcome {
+            CheckOutcome::Unchanged => {}
+            CheckOutcome::Updated => self.updated += 1,
+            CheckOutcome::Suspended => self.suspended += 1,
+            CheckOutcome::Removed => self.removed += 1,
+            CheckOutcome::Failed => self.failed += 1,
+        }
+    }
+}
+
+// persist the result of checking one instance, in a transaction of its own
+#[allow(clippy::too_many_lines)]
+fn store_instance_check(conn: &mut SqliteConnection, result: InstanceCheckResult) -> CheckOutcome {
+    use super::schema::headers::dsl::headers;
+    use super::schema::instance_events::dsl::instance_events;
+    use super::schema::instances::dsl::failures;
+    use super::schema::scans::dsl::{details, instance_id, percent, rating, scanner, scans};
+
+    let instance_url = result.instance_url;
+    let Some(old_status) = result.instance_status else {
+        println!("    error storing the check, the instance has an unknown status");
+        return CheckOutcome::Failed;
+    };
+
+    // robots.txt must have changed, so no tombstone is kept, that would keep getting checked
+    if matches!(result.error, Some(DirectoryError::RobotsDisallowed(_))) {
+        return match delete(instances.filter(id.eq(result.instance_id))).execute(conn) {
+            Ok(_) => {
+                print!("    removed the instance, due to: {}", result.message);
+                CheckOutcome::Removed
+            }
+            Err(e) => {
+                println!("    error removing the instance: {e:?}");
+                CheckOutcome::Failed
+            }
+        };
+    }
+
+    // site no longer an instance, take it off the list, suspended instances and tombstones keep
+    // their status, so they still expire
+    let new_status = match result.error {
+        Some(DirectoryError::NotPrivateBin(_)) if old_status.is_listed() => {
+            InstanceStatus::Suspended
+        }
+        Some(DirectoryError::NotPrivateBin(_)) => old_status,
+        Some(_) => return CheckOutcome::Failed,
+        // a successful check brings back degraded, suspended and removed instances
+        None => InstanceStatus::Active,
+    };
+    if new_status != InstanceStatus::Active {
+        if new_status == old_status {
+            return CheckOutcome::Unchanged;
+        }
+        return match conn.transaction(|conn| {
+            set_instance_status(conn, result.instance_id, old_status, new_status)
+        }) {
+            Ok(()) => {
+                print!(
+                    "    {} the instance, due to: {}",
+                    new_status.as_str(),
+                    result.message
+                );
+                CheckOutcome::Suspended
             }
-            println!("all scan update queries concluded ({:?})", timer.elapsed());
-
-            // delete checks and instances that failed too many times
-            let timer = Instant::now();
-            match sql_query(format!(
-                "DELETE FROM instances \
-                WHERE id in ( \
-                    SELECT instance_id \
-                    FROM checks \
-                    WHERE up = 0 \
-                    GROUP BY instance_id \
-                    HAVING COUNT(up) >= {MAX_FAILURES} \
-                );"
+            Err(e) => {
+                println!("    error taking the instance off the list: {e:?}");
+                CheckOutcome::Failed
+            }
+        };
+    }
+
+    if result.instance_update.is_none()
+        && result.scan_updates.is_empty()
+        && result.header_updates.is_empty()
+        && result.events.is_empty()
+        && old_status == new_status
+    {
+        return CheckOutcome::Unchanged;
+    }
+    let timer = Instant::now();
+    let mut success = String::new();
+    match conn.transaction(|conn| {
+        if old_status != new_status {
+            set_instance_status(conn, result.instance_id, old_status, new_status)?;
+            update(instances.filter(id.eq(result.instance_id)))
+                .set(failures.eq(0))
+                .execute(conn)?;
+            let _ = writeln!(
+                &mut success,
+                "Instance {instance_url} was {}, re-activated",
+                old_status.as_str()
+            );
+        }
+        if let Some(updated_instance) = result.instance_update {
+            let count = update(instances.filter(id.eq(result.instance_id)))
+                .set((
+                    version.eq(updated_instance.version),
+                    https.eq(updated_instance.https),
+                    https_redirect.eq(updated_instance.https_redirect),
+                    csp_header.eq(updated_instance.csp_header),
+                    csp_policy.eq(updated_instance.csp_policy),
+                    attachments.eq(updated_instance.attachments),
+                    country_id.eq(updated_instance.country_id),
+                    updated_instance.features,
+                ))
+                .execute(conn)?;
+            // the events describe this update, so they can't get recorded without it
+            if count == 0 {
+                return Err(diesel::result::Error::NotFound);
+            }
+            success.push_str(&result.instance_update_success);
+        }
+        for (updated_scan, scan_update_success) in result.scan_updates {
+            // scanners that got added since the last full check have no scan to update yet
+            let count = update(
+                scans
+                    .filter(instance_id.eq(updated_scan.instance_id))
+                    .filter(scanner.eq(&updated_scan.scanner)),
+            )
+            .set((
+                rating.eq(&updated_scan.rating),
+                percent.eq(updated_scan.percent),
+                details.eq(&updated_scan.details),
             ))
-            .execute(&mut conn)
-            {
-                Ok(count) => println!(
-                    "removed {count} instances that failed too many times ({:?})",
-                    timer.elapsed()
-                ),
-                Err(e) => {
-                    println!("error removing instances failing too many times: {e:?}");
-                }
+            .execute(conn)?;
+            if count == 0 {
+                insert_into(scans).values(&updated_scan).execute(conn)?;
             }
+            success.push_str(&scan_update_success);
+        }
+        if !result.header_updates.is_empty() {
+            replace_into(headers)
+                .values(&result.header_updates)
+                .execute(conn)?;
+            let _ = writeln!(
+                &mut success,
+                "Instance {instance_url} security headers updated"
+            );
+        }
+        if !result.events.is_empty() {
+            insert_into(instance_events)
+                .values(&result.events)
+                .execute(conn)?;
+        }
+        QueryResult::Ok(())
+    }) {
+        Ok(()) => {
+            print!("{success}");
+            println!(
+                "Instance {instance_url} results stored ({:?})",
+                timer.elapsed()
+            );
+            CheckOutcome::Updated
         }
         Err(e) => {
-            println!("failed retrieving instances from database with error: {e:?}");
+            println!("Instance {instance_url} failed to be updated with error: {e:?}");
+            CheckOutcome::Failed
+        }
+    }
+}
+
+// remove instances that stayed suspended for too long and purge the expired tombstones
+fn expire_instances(conn: &mut SqliteConnection, config: &DirectoryConfig) {
+    use super::schema::instances::dsl::{status, status_changed};
+
+    let timer = Instant::now();
+    let suspension_cutoff = get_epoch().saturating_sub(config.suspension_days * 86400);
+    match conn.transaction(|conn| {
+        let expired: Vec<i32> = instances
+            .select(id)
+            .filter(status.eq(InstanceStatus::Suspended.as_str()))
+            .filter(status_changed.lt(sql(&format!("datetime({suspension_cutoff}, 'unixepoch')"))))
+            .load(conn)?;
+        for instance_id in &expired {
+            set_instance_status(
+                conn,
+                *instance_id,
+                InstanceStatus::Suspended,
+                InstanceStatus::Removed,
+            )?;
         }
+        QueryResult::Ok(expired.len())
+    }) {
+        Ok(count) => println!(
+            "removed {count} instances suspended for too long ({:?})",
+            timer.elapsed()
+        ),
+        Err(e) => println!("error removing instances suspended for too long: {e:?}"),
+    }
+
+    let timer = Instant::now();
+    let tombstone_cutoff = get_epoch().saturating_sub(config.tombstone_days * 86400);
+    match delete(
+        instances
+            .filter(status.eq(InstanceStatus::Removed.as_str()))
+            .filter(status_changed.lt(sql(&format!("datetime({tombstone_cutoff}, 'unixepoch')")))),
+    )
+    .execute(conn)
+    {
+        Ok(count) => println!(
+            "purged {count} instances removed before {tombstone_cutoff} ({:?})",
+            timer.elapsed()
+        ),
+        Err(e) => println!("error purging removed instances: {e:?}"),
     }
 }
 
 #[allow(clippy::too_many_lines)]
-async fn check_instance(instance: &Instance) -> InstanceCheckResult<'_> {
+async fn check_instance(instance: &Instance) -> InstanceCheckResult {
     let timer = Instant::now();
     let mut message = String::new();
-    let mut instance_options = [
+    let mut instance_options = vec![
         ("version", instance.version.clone(), String::new()),
         ("https", format!("{:?}", instance.https), String::new()),
         (
@@ -185,11 +496,15 @@ async fn check_instance(instance: &Instance) -> InstanceCheckResult<'_> {
         ),
         ("country_id", instance.country_id.clone(), String::new()),
     ];
-    let mut scan: ScanNew;
+    for (label, value) in instance.features.fields() {
+        instance_options.push((label, value, String::new()));
+    }
     let mut instance_update = None;
     let mut instance_update_success = String::new();
-    let mut scan_update = None;
-    let mut scan_update_success = String::new();
+    let mut scan_updates = vec![];
+    let mut header_updates = vec![];
+    let mut events = vec![];
+    let mut error = None;
     let instance_url = instance.url.clone();
     match PrivateBin::new(instance.url.clone()).await {
         Ok(privatebin) => {
@@ -205,22 +520,38 @@ async fn check_instance(instance: &Instance) -> InstanceCheckResult<'_> {
                 .instance
                 .country_id
                 .clone_into(&mut instance_options[5].2);
+            for (option, (_, value)) in instance_options[6..]
+                .iter_mut()
+                .zip(privatebin.instance.features.fields())
+            {
+                option.2 = value;
+            }
             let elapsed = timer.elapsed();
-            let timer = Instant::now();
-            if instance_options.iter().any(|x| x.1 != x.2) {
+            // the policy only gets stored to explain the CSP check, it doesn't warrant an event
+            let policy_changed = privatebin.instance.csp_policy != instance.csp_policy;
+            if policy_changed || instance_options.iter().any(|x| x.1 != x.2) {
                 instance_update = Some(privatebin.instance);
                 let _ = writeln!(
                     &mut instance_update_success,
                     "Instance {instance_url} checked and updated ({elapsed:?}):"
                 );
-                for (label, old, new) in &instance_options {
+                for (label, old, new) in instance_options {
                     if old != new {
                         let _ = writeln!(
                             &mut instance_update_success,
                             "    {label} was {old}, updated to {new}"
                         );
+                        events.push(InstanceEventNew {
+                            field: label.into(),
+                            old_value: old,
+                            new_value: new,
+                            instance_id: instance.id,
+                        });
                     }
                 }
+                if policy_changed {
+                    instance_update_success.push_str("    csp_policy updated\n");
+                }
             } else {
                 let _ = writeln!(
                     &mut message,
@@ -228,116 +559,174 @@ async fn check_instance(instance: &Instance) -> InstanceCheckResult<'_> {
                 );
             }
 
-            // retrieve latest scan
-            scan = privatebin.scans[0].clone();
-            // if missing, wait for the scan to conclude and poll again
-            let rating = scan.rating.clone();
-            if rating == "-" {
-                sleep(Duration::from_secs(5)).await;
-                scan = PrivateBin::check_rating_mozilla_observatory(&instance_url).await;
+            // replace the security header evaluations, if any of them changed
+            if HeaderChecks::from(privatebin.headers.as_slice()).0 != instance.headers.0 {
+                header_updates = privatebin
+                    .headers
+                    .into_iter()
+                    .map(|header| HeaderNew {
+                        instance_id: instance.id,
+                        ..header
+                    })
+                    .collect();
             }
-            let elapsed = timer.elapsed();
-            if rating != "-" && rating != instance.rating_mozilla_observatory {
-                scan_update = Some(scan.clone());
-                let _ = writeln!(
-                    &mut scan_update_success,
-                    "Instance {instance_url} rating updated to: {rating} ({elapsed:?})"
-                );
-            } else {
-                let _ = writeln!(
-                    &mut scan_update_success,
-                    "Instance {instance_url} rating remains unchanged at: {rating} ({elapsed:?})"
-                );
+
+            // retrieve latest scans
+            for (scanner, mut scan) in SCANNERS.iter().zip(privatebin.scans) {
+                let timer = Instant::now();
+                // if missing, wait for the scan to conclude and poll again
+                if scan.rating == "-" && scanner.retry_pending() {
+                    sleep(Duration::from_secs(5)).await;
+                    scan = scanner.scan(&instance_url).await;
+                }
+                let elapsed = timer.elapsed();
+                let name = scanner.name();
+                let rating = scan.rating.clone();
+                let old_rating = instance.ratings.get(name);
+                if rating == "-" || (rating == old_rating && scan.details == "{}") {
+                    let _ = writeln!(
+                        &mut message,
+                        "Instance {instance_url} {name} rating remains unchanged at: {old_rating} ({elapsed:?})"
+                    );
+                    continue;
+                }
+                scan.instance_id = instance.id;
+                if rating != old_rating {
+                    events.push(InstanceEventNew {
+                        field: format!("rating_{name}"),
+                        old_value: old_rating.into(),
+                        new_value: rating.clone(),
+                        instance_id: instance.id,
+                    });
+                    scan_updates.push((
+                        scan,
+                        format!("Instance {instance_url} {name} rating updated to: {rating} ({elapsed:?})\n"),
+                    ));
+                } else if scan.details != "{}" {
+                    // details, like certificate expiry, may change without affecting the rating
+                    scan_updates.push((
+                        scan,
+                        format!("Instance {instance_url} {name} details updated ({elapsed:?})\n"),
+                    ));
+                }
             }
         }
         Err(e) => {
             let _ = writeln!(
                 &mut message,
-                "Instance {instance_url} failed to be checked with error: {e:?}"
+                "Instance {instance_url} failed to be checked with error: {e}"
             );
+            error = Some(e);
         }
     }
 
     InstanceCheckResult {
         message,
-        scan_update,
-        scan_update_success,
-        instance,
+        error,
+        scan_updates,
+        header_updates,
+        events,
+        instance_id: instance.id,
+        instance_url,
+        instance_status: InstanceStatus::from_name(&instance.status),
         instance_update,
         instance_update_success,
     }
 }
 
-async fn check_instance_up(instance: &Instance) -> (&String, CheckNew, Duration) {
+async fn check_instance_up(instance: &Instance) -> (&Instance, CheckNew, Duration) {
     // measure instance being up or down
-    let timer = Instant::now();
+    let (up, status, elapsed) = instance.check_up().await;
     let check_result = CheckNew {
-        up: instance.check_up().await,
+        up,
         instance_id: instance.id,
+        latency: i32::try_from(elapsed.as_millis()).unwrap_or(i32::MAX),
+        status: status.into(),
     };
-    (&instance.url, check_result, timer.elapsed())
+    (instance, check_result, elapsed)
 }
 
-/// # Panics
-///
-/// May panic in `Config::from`.
-pub async fn check_up(rocket: Rocket<Build>) {
+/// check whether the instances are up and store the results
+pub async fn check_up(db: &DirectoryDbConn, config: &DirectoryConfig) {
     use super::schema::checks::dsl::{checks, updated};
 
-    let directory_config =
-        Config::from("directory", &rocket).expect("configuration of directory database");
-    let mut conn = SqliteConnection::establish(&directory_config.url)
-        .expect("connection to directory database");
-    let cached_instances = get_instances().load::<Instance>(&mut conn);
+    let cached_instances = db
+        .run(|conn| get_monitored_instances().load::<Instance>(conn))
+        .await;
     match cached_instances {
         Ok(instance_list) => {
             let mut instance_checks = vec![];
-            let mut children = vec![];
-            for instance in &instance_list {
-                children.push(check_instance_up(instance));
-            }
-            let mut pinned_children: Vec<_> = children.into_iter().map(Box::pin).collect();
-            while !pinned_children.is_empty() {
-                let ((instance_url, instance_check, elapsed), _index, remaining_children) =
-                    select_all(pinned_children).await;
+            let mut status_updates = vec![];
+            let mut results = stream::iter(&instance_list)
+                .map(check_instance_up)
+                .buffer_unordered(config.max_concurrent_checks);
+            while let Some((instance, instance_check, elapsed)) = results.next().await {
+                let consecutive_failures = if instance_check.up {
+                    0
+                } else {
+                    instance.failures.saturating_add(1)
+                };
+                let Some(old_status) = InstanceStatus::from_name(&instance.status) else {
+                    println!(
+                        "Instance {} has the unknown status {}, only its check gets stored",
+                        instance.url, instance.status
+                    );
+                    instance_checks.push(instance_check);
+                    continue;
+                };
+                let new_status = old_status.after_check(
+                    u64::try_from(consecutive_failures).unwrap_or_default(),
+                    config,
+                );
+                if consecutive_failures != instance.failures || new_status != old_status {
+                    status_updates.push(StatusUpdate {
+                        instance_id: instance.id,
+                        failures: consecutive_failures,
+                        old_status,
+                        new_status,
+                    });
+                }
                 instance_checks.push(instance_check);
-                println!("Instance {instance_url} checked ({elapsed:?})");
-                pinned_children = remaining_children;
+                println!("Instance {} checked ({elapsed:?})", instance.url);
             }
 
-            // store checks
-            let timer = Instant::now();
-            match insert_into(checks)
-                .values(&instance_checks)
-                .execute(&mut conn)
-            {
-                Ok(_) => {
-                    println!("stored uptime checks ({:?})", timer.elapsed());
-                    let timer = Instant::now();
-
-                    // delete checks older then:
-                    let cutoff = get_epoch() - ((CHECKS_TO_STORE - 1) * CRON_INTERVAL);
-                    match delete(checks)
-                        .filter(updated.lt(sql(&format!("datetime({cutoff}, 'unixepoch')"))))
-                        .execute(&mut conn)
-                    {
-                        Ok(_) => {
-                            println!(
-                                "cleaned up checks stored before {cutoff} ({:?})",
-                                timer.elapsed()
-                            );
-                        }
-                        Err(e) => {
-                            println!(
-                                "failed to cleanup checks stored before {cutoff}, with error: {e:?}"
-                            );
+            // delete checks older then:
+            let cutoff =
+                get_epoch().saturating_sub((config.checks_to_store - 1) * config.cron_interval);
+            let uptimes_to_store = config.uptimes_to_store;
+            db.run(move |conn| {
+                // store checks
+                let timer = Instant::now();
+                match insert_into(checks).values(&instance_checks).execute(conn) {
+                    Ok(_) => {
+                        println!("stored uptime checks ({:?})", timer.elapsed());
+                        store_statuses(conn, &status_updates);
+                        // aggregate checks, before they get deleted
+                        store_uptimes(conn, uptimes_to_store);
+                        let timer = Instant::now();
+                        match delete(checks)
+                            .filter(updated.lt(sql(&format!("datetime({cutoff}, 'unixepoch')"))))
+                            .execute(conn)
+                        {
+                            Ok(_) => {
+                                println!(
+                                    "cleaned up checks stored before {cutoff} ({:?})",
+                                    timer.elapsed()
+                                );
+                            }
+                            Err(e) => {
+                                println!(
+                                    "failed to cleanup checks stored before {cutoff}, with error: {e:?}"
+                                );
+                            }
                         }
                     }
+                    Err(e) => {
+                        println!("failed to store uptime checks with error: {e:?}");
+                    }
                 }
-                Err(e) => {
-                    println!("failed to store uptime checks with error: {e:?}");
-                }
-            }
+            })
+            .await;
         }
         Err(e) => {
             println!("failed retrieving instances from database with error: {e:?}");
@@ -345,31 +734,124 @@ pub async fn check_up(rocket: Rocket<Build>) {
     }
 }
 
+// track consecutive failures and the resulting status transitions
+fn store_statuses(conn: &mut SqliteConnection, status_updates: &[StatusUpdate]) {
+    use super::schema::instances::dsl::failures;
+
+    let timer = Instant::now();
+    match conn.transaction(|conn| {
+        for status_update in status_updates {
+            update(instances.filter(id.eq(status_update.instance_id)))
+                .set(failures.eq(status_update.failures))
+                .execute(conn)?;
+            if status_update.new_status != status_update.old_status {
+                set_instance_status(
+                    conn,
+                    status_update.instance_id,
+                    status_update.old_status,
+                    status_update.new_status,
+                )?;
+                println!(
+                    "Instance ID {} changed from {} to {}",
+                    status_update.instance_id,
+                    status_update.old_status.as_str(),
+                    status_update.new_status.as_str()
+                );
+            }
+        }
+        QueryResult::Ok(())
+    }) {
+        Ok(()) => println!(
+            "stored {} failure counts and statuses ({:?})",
+            status_updates.len(),
+            timer.elapsed()
+        ),
+        Err(e) => println!("failed to store failure counts and statuses, with error: {e:?}"),
+    }
+}
+
+fn store_uptimes(conn: &mut SqliteConnection, uptimes_to_store: u64) {
+    for resolution in UptimeResolution::ALL {
+        let timer = Instant::now();
+        let label = resolution.as_str();
+        let format = resolution.period_format();
+        // the current period gets updated as its checks come in, while periods whose oldest
+        // checks got cleaned up already keep their more complete aggregate - the WHERE clause
+        // resolves the ambiguity of an ON CONFLICT following a SELECT, as SQLite documents
+        match sql_query(format!(
+            "INSERT INTO uptimes (resolution, period, checks, up, instance_id) \
+            SELECT '{label}', strftime('{format}', updated) AS period, \
+                COUNT(up), SUM(up), instance_id \
+            FROM checks \
+            WHERE true \
+            GROUP BY instance_id, period \
+            ON CONFLICT (instance_id, resolution, period) DO UPDATE \
+            SET checks = excluded.checks, up = excluded.up \
+            WHERE excluded.checks > uptimes.checks;"
+        ))
+        .execute(conn)
+        {
+            Ok(count) => println!("stored {count} {label} uptimes ({:?})", timer.elapsed()),
+            Err(e) => println!("failed to store {label} uptimes, with error: {e:?}"),
+        }
+    }
+
+    // delete uptimes older then:
+    let timer = Instant::now();
+    let cutoff = get_epoch().saturating_sub(uptimes_to_store * 86400);
+    match sql_query(format!(
+        "DELETE FROM uptimes WHERE period < datetime({cutoff}, 'unixepoch');"
+    ))
+    .execute(conn)
+    {
+        Ok(_) => println!(
+            "cleaned up uptimes stored before {cutoff} ({:?})",
+            timer.elapsed()
+        ),
+        Err(e) => println!("failed to cleanup uptimes stored before {cutoff}, with error: {e:?}"),
+    }
+}
+
 #[tokio::test]
 #[allow(clippy::too_many_lines)]
 async fn add_update_and_delete() {
+    use super::functions::get_instances;
+    use super::models::InstanceFeatures;
+    use super::rocket;
     use super::schema::checks::dsl::*;
-    use super::schema::{instances, scans};
-    use super::{CRON_INTERVAL, rocket};
+    use super::schema::{instance_events, instances, scan_runs, scans, uptimes};
+    use super::test_support::{Fixture, establish_connection, mock_url, serve};
     use diesel::prelude::*;
 
-    let directory_config = rocket_sync_db_pools::Config::from("directory", &rocket())
-        .expect("configuration of directory database");
-    let mut conn = SqliteConnection::establish(&directory_config.url)
-        .expect("connection to directory database");
+    let instance_url_of = |conn: &mut SqliteConnection, instance: i32| -> String {
+        instances::table
+            .select(instances::url)
+            .filter(instances::id.eq(instance))
+            .first(conn)
+            .expect("selecting instance URL")
+    };
+
+    let mut conn = establish_connection();
+    let rocket = rocket().ignite().await.expect("ignited rocket");
+    let db = DirectoryDbConn::get_one(&rocket)
+        .await
+        .expect("database connection");
+    let config = DirectoryConfig::default();
     let empty: Vec<i32> = vec![]; // needs an explicit type, as it can't be inferred from an immutable, empty vector
     let now = get_epoch();
 
     // insert an instance
     let instance = InstanceNew {
         id: Some(1),
-        url: "https://privatebin.net".into(),
+        url: serve("/privatebin", Fixture::privatebin("2.0.1")),
         version: "1.4.0".into(),
         https: true,
         https_redirect: true,
         country_id: "CH".into(),
         attachments: false,
         csp_header: false,
+        csp_policy: String::new(),
+        features: InstanceFeatures::default(),
     };
     insert_into(instances)
         .values(&instance)
@@ -382,6 +864,7 @@ async fn add_update_and_delete() {
         rating: "-".into(),
         percent: 0,
         instance_id: 1,
+        details: "{}".into(),
     };
     insert_into(scans::table)
         .values(&scan)
@@ -411,7 +894,7 @@ async fn add_update_and_delete() {
         .expect("selecting oldest check");
     assert_eq!(vec![1], oldest_check);
 
-    check_up(rocket()).await;
+    check_up(&db, &config).await;
     let oldest_check: Vec<i32> = checks
         .select(instance_id)
         .filter(updated.eq(&format!("{oldest_update}")))
@@ -419,21 +902,39 @@ async fn add_update_and_delete() {
         .expect("selecting oldest check, now deleted");
     assert_eq!(empty, oldest_check);
 
-    // insert another instance, subsequently to be deleted
+    // the hours and days got aggregated before the checks got deleted
+    for resolution in UptimeResolution::ALL {
+        let aggregated_uptimes: Vec<i32> = uptimes::table
+            .select(uptimes::up)
+            .filter(uptimes::instance_id.eq(1))
+            .filter(uptimes::resolution.eq(resolution.as_str()))
+            .load(&mut conn)
+            .expect("selecting aggregated uptimes");
+        assert!(!aggregated_uptimes.is_empty());
+    }
+
+    // insert another instance, subsequently to be suspended, removed and purged
     let instance = InstanceNew {
         id: Some(2),
-        url: "http://zerobin-legacy.dssr.ch".into(),
+        url: mock_url("/unavailable"),
         version: "0.20".into(),
         https: true,
         https_redirect: false,
         country_id: "CH".into(),
         attachments: false,
         csp_header: true,
+        csp_policy: String::new(),
+        features: InstanceFeatures::default(),
     };
     insert_into(instances)
         .values(&instance)
         .execute(&mut conn)
         .expect("inserting instance ID 2");
+    update(instances)
+        .filter(instances::id.eq(2))
+        .set(instances::failures.eq(i32::try_from(MAX_FAILURES - 1).unwrap()))
+        .execute(&mut conn)
+        .expect("setting the consecutive failures of instance ID 2");
 
     // insert scan
     let scan = ScanNew {
@@ -441,6 +942,7 @@ async fn add_update_and_delete() {
         rating: "-".into(),
         percent: 0,
         instance_id: 2,
+        details: "{}".into(),
     };
     insert_into(scans::table)
         .values(&scan)
@@ -462,7 +964,75 @@ async fn add_update_and_delete() {
         .execute(&mut conn)
         .expect("inserting test checks for instance ID 2");
 
-    check_full(rocket()).await;
+    // one more failure suspends it, taking it off the list, but it keeps getting checked
+    let instance_status = |conn: &mut SqliteConnection, instance: i32| -> String {
+        instances::table
+            .select(instances::status)
+            .filter(instances::id.eq(instance))
+            .first(conn)
+            .expect("selecting instance status")
+    };
+    check_up(&db, &config).await;
+    assert_eq!(instance_status(&mut conn, 2), "suspended");
+    let listed: Vec<Instance> = get_instances().load(&mut conn).expect("listed instances");
+    assert!(listed.iter().all(|instance| instance.id != 2));
+    let monitored: Vec<Instance> = get_monitored_instances()
+        .load(&mut conn)
+        .expect("monitored instances");
+    assert!(monitored.iter().any(|instance| instance.id == 2));
+    let transitions: Vec<String> = instance_events::table
+        .select(instance_events::new_value)
+        .filter(instance_events::instance_id.eq(2))
+        .filter(instance_events::field.eq("status"))
+        .load(&mut conn)
+        .expect("selecting status events of instance 2");
+    assert_eq!(transitions, vec!["suspended"]);
+
+    // once suspended for long enough, it gets removed and later purged
+    let backdate = |conn: &mut SqliteConnection, days: u64| {
+        let changed = now - ((days + 1) * 86400);
+        update(instances::table)
+            .filter(instances::id.eq(2))
+            .set(instances::status_changed.eq(sql(&format!("datetime({changed}, 'unixepoch')"))))
+            .execute(conn)
+            .expect("backdating status change of instance ID 2");
+    };
+    backdate(&mut conn, config.suspension_days);
+    expire_instances(&mut conn, &config);
+    assert_eq!(instance_status(&mut conn, 2), "removed");
+    let kept_check: Vec<i32> = checks
+        .select(instance_id)
+        .filter(instance_id.eq(2))
+        .load(&mut conn)
+        .expect("selecting checks for instance 2, kept as tombstone");
+    assert!(!kept_check.is_empty());
+    // a tombstone that keeps failing the full checks stays removed and still gets purged
+    backdate(&mut conn, config.tombstone_days);
+    let result = InstanceCheckResult {
+        message: String::new(),
+        error: Some(DirectoryError::NotPrivateBin(mock_url("/unavailable"))),
+        scan_updates: vec![],
+        header_updates: vec![],
+        events: vec![],
+        instance_id: 2,
+        instance_url: mock_url("/unavailable"),
+        instance_status: Some(InstanceStatus::Removed),
+        instance_update: None,
+        instance_update_success: String::new(),
+    };
+    assert!(matches!(
+        store_instance_check(&mut conn, result),
+        CheckOutcome::Unchanged
+    ));
+    assert_eq!(instance_status(&mut conn, 2), "removed");
+    let transitions: Vec<String> = instance_events::table
+        .select(instance_events::new_value)
+        .filter(instance_events::instance_id.eq(2))
+        .filter(instance_events::field.eq("status"))
+        .load(&mut conn)
+        .expect("selecting status events of instance 2");
+    assert_eq!(transitions, vec!["suspended", "removed"]);
+    expire_instances(&mut conn, &config);
     let deleted_check: Vec<i32> = checks
         .select(instance_id)
         .filter(instance_id.eq(2))
@@ -482,29 +1052,288 @@ async fn add_update_and_delete() {
         .expect("selecting instance 2, now deleted");
     assert_eq!(empty, deleted_instance);
 
-    // check immediate removal of sites that are no longer PrivateBin instances
+    // check immediate suspension of sites that are no longer PrivateBin instances
+    let privatebin_url = instance_url_of(&mut conn, 1);
     update(instances)
         .filter(instances::id.eq(1))
-        .set(instances::url.eq("https://privatebin.info".to_string()))
+        .set(instances::url.eq(serve(
+            "/homepage",
+            Fixture::html("<p>Not a PrivateBin instance.</p>"),
+        )))
         .execute(&mut conn)
         .expect("manipulating instance ID 1 to point to a non-PrivateBin URL");
-    check_full(rocket()).await;
-    let deleted_check: Vec<i32> = checks
+    check_full(&db, &config).await;
+    assert_eq!(instance_status(&mut conn, 1), "suspended");
+    let kept_check: Vec<i32> = checks
         .select(instance_id)
         .filter(instance_id.eq(1))
         .load(&mut conn)
-        .expect("selecting check for instance 1, now deleted");
-    assert_eq!(empty, deleted_check);
-    let deleted_scan: Vec<i32> = scans::table
-        .select(scans::instance_id)
-        .filter(scans::instance_id.eq(1))
-        .load(&mut conn)
-        .expect("selecting scan for instance 1, now deleted");
-    assert_eq!(empty, deleted_scan);
-    let deleted_instance: Vec<i32> = instances::table
+        .expect("selecting checks for instance 1, kept while suspended");
+    assert!(!kept_check.is_empty());
+
+    // the full check got recorded, including the suspension
+    let (suspended_count, removed_count, finished): (i32, i32, Option<String>) = scan_runs::table
+        .select((
+            scan_runs::suspended,
+            scan_runs::removed,
+            scan_runs::finished,
+        ))
+        .order(scan_runs::id.desc())
+        .first(&mut conn)
+        .expect("selecting the latest full check");
+    assert_eq!((suspended_count, removed_count), (1, 0));
+    assert!(finished.is_some());
+
+    // once it is a PrivateBin instance again, it gets re-activated
+    update(instances)
+        .filter(instances::id.eq(1))
+        .set(instances::url.eq(privatebin_url))
+        .execute(&mut conn)
+        .expect("pointing instance ID 1 back to its PrivateBin URL");
+    check_full(&db, &config).await;
+    assert_eq!(instance_status(&mut conn, 1), "active");
+
+    // instances disallowing the directory in their robots.txt don't get kept as tombstones
+    let result = InstanceCheckResult {
+        message: String::new(),
+        error: Some(DirectoryError::RobotsDisallowed(instance_url_of(
+            &mut conn, 1,
+        ))),
+        scan_updates: vec![],
+        header_updates: vec![],
+        events: vec![],
+        instance_id: 1,
+        instance_url: instance_url_of(&mut conn, 1),
+        instance_status: Some(InstanceStatus::Active),
+        instance_update: None,
+        instance_update_success: String::new(),
+    };
+    assert!(matches!(
+        store_instance_check(&mut conn, result),
+        CheckOutcome::Removed
+    ));
+    let removed: Vec<i32> = instances::table
         .select(instances::id)
         .filter(instances::id.eq(1))
         .load(&mut conn)
         .expect("selecting instance 1, now deleted");
-    assert_eq!(empty, deleted_instance);
+    assert_eq!(empty, removed);
+
+    delete(instances::table.filter(instances::id.eq(1)))
+        .execute(&mut conn)
+        .expect("deleting instance ID 1");
+}
+
+#[tokio::test]
+async fn scheduled_uptime_checks() {
+    use super::models::InstanceFeatures;
+    use super::rocket;
+    use super::schema::checks::dsl::{checks, instance_id, up};
+    use super::test_support::{Fixture, serve};
+
+    let rocket = rocket().ignite().await.expect("ignited rocket");
+    let pool = DirectoryDbConn::pool(&rocket)
+        .cloned()
+        .expect("database pool");
+    let db = DirectoryDbConn::from_pool(&pool)
+        .await
+        .expect("database connection");
+    let instance = InstanceNew {
+        id: Some(3),
+        url: serve("/scheduled", Fixture::privatebin("2.0.1")),
+        version: "2.0.1".into(),
+        https: false,
+        https_redirect: false,
+        country_id: "AQ".into(),
+        attachments: true,
+        csp_header: true,
+        csp_policy: String::new(),
+        features: InstanceFeatures::default(),
+    };
+    db.run(move |conn| {
+        insert_into(instances)
+            .values(&instance)
+            .execute(conn)
+            .expect("inserting instance ID 3");
+        insert_into(super::schema::scans::table)
+            .values(ScanNew::new("mozilla_observatory", "A+", 3))
+            .execute(conn)
+            .expect("inserting scan for instance ID 3");
+        // only instances with checks get listed
+        insert_into(checks)
+            .values((up.eq(true), instance_id.eq(3)))
+            .execute(conn)
+            .expect("inserting check for instance ID 3");
+    })
+    .await;
+
+    let config = DirectoryConfig {
+        cron_interval: 1,
+        scheduler_jitter: 0,
+        ..DirectoryConfig::default()
+    };
+    let stored_checks = async || -> i64 {
+        db.run(|conn| {
+            checks
+                .filter(instance_id.eq(3))
+                .count()
+                .get_result(conn)
+                .expect("counting checks of instance ID 3")
+        })
+        .await
+    };
+    tokio::time::pause();
+    let shutdown = rocket.shutdown();
+    let scheduled = tokio::spawn(schedule(
+        ScheduledCheck::Up,
+        pool,
+        Arc::new(config),
+        shutdown.clone(),
+    ));
+    // each advance fires the interval, ticks during a run still in progress get skipped
+    let started = Instant::now();
+    while stored_checks().await < 3 {
+        assert!(
+            started.elapsed() < Duration::from_secs(10),
+            "scheduled uptime checks didn't run"
+        );
+        tokio::time::advance(Duration::from_secs(1)).await;
+    }
+    shutdown.notify();
+    scheduled.await.expect("scheduler stopped on shutdown");
+
+    db.run(|conn| {
+        delete(instances.filter(id.eq(3)))
+            .execute(conn)
+            .expect("deleting instance ID 3")
+    })
+    .await;
+}
+
+#[tokio::test]
+#[allow(clippy::too_many_lines)]
+async fn aggregated_uptimes() {
+    use super::models::InstanceFeatures;
+    use super::rocket;
+    use super::schema::checks::dsl::{checks, instance_id, up, updated};
+    use super::test_support::{Fixture, serve};
+    use rocket::http::{Accept, Status};
+    use rocket::local::asynchronous::Client;
+    use rocket::serde::json::Value;
+
+    let client = Client::untracked(rocket())
+        .await
+        .expect("valid rocket instance");
+    let db = DirectoryDbConn::get_one(client.rocket())
+        .await
+        .expect("database connection");
+    let instance = InstanceNew {
+        id: Some(4),
+        url: serve("/aggregated", Fixture::privatebin("2.0.1")),
+        version: "2.0.1".into(),
+        https: false,
+        https_redirect: false,
+        country_id: "AQ".into(),
+        attachments: true,
+        csp_header: true,
+        csp_policy: String::new(),
+        features: InstanceFeatures::default(),
+    };
+    db.run(move |conn| {
+        insert_into(instances)
+            .values(&instance)
+            .execute(conn)
+            .expect("inserting instance ID 4");
+    })
+    .await;
+
+    // checks of two hours of the day before yesterday, all but one of the first hour up
+    let day = i64::try_from(get_epoch() / 86400 - 2).expect("current day") * 86400;
+    let first_hour = day + 10 * 3600;
+    let second_hour = first_hour + 3600;
+    let insert_checks = move |conn: &mut SqliteConnection, values: &[(i64, bool)]| {
+        for (time, is_up) in values {
+            insert_into(checks)
+                .values((
+                    updated.eq(sql(&format!("datetime({time}, 'unixepoch')"))),
+                    up.eq(is_up),
+                    instance_id.eq(4),
+                ))
+                .execute(conn)
+                .expect("inserting check for instance ID 4");
+        }
+    };
+    let uptimes = async |query: &str| -> Value {
+        let response = client
+            .get(format!("/api/instance/4/uptime?{query}"))
+            .header(Accept::JSON)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok, "status of {query}");
+        response.into_json().await.expect("valid JSON")
+    };
+    db.run(move |conn| {
+        insert_checks(
+            conn,
+            &[
+                (first_hour, true),
+                (first_hour + 300, false),
+                (first_hour + 600, true),
+                (second_hour, true),
+            ],
+        );
+        store_uptimes(conn, UPTIMES_TO_STORE);
+    })
+    .await;
+    let range = format!("from={day}&to={}", day + 86400);
+    assert_eq!(
+        uptimes(&format!("resolution=hour&{range}")).await,
+        rocket::serde::json::json!([
+            { "period": first_hour, "checks": 3, "uptime": 66 },
+            { "period": second_hour, "checks": 1, "uptime": 100 },
+        ])
+    );
+    assert_eq!(
+        uptimes(&range).await,
+        rocket::serde::json::json!([{ "period": day, "checks": 4, "uptime": 75 }])
+    );
+    // the end of the range is exclusive
+    assert_eq!(
+        uptimes(&format!("resolution=hour&from={day}&to={second_hour}")).await,
+        rocket::serde::json::json!([{ "period": first_hour, "checks": 3, "uptime": 66 }])
+    );
+    // by default, the 30 days before the end of the range get returned
+    assert_eq!(
+        uptimes(&format!("to={day}")).await,
+        rocket::serde::json::json!([])
+    );
+
+    // periods get updated with later checks, but not by the remainder of deleted ones
+    db.run(move |conn| {
+        insert_checks(conn, &[(second_hour + 300, false)]);
+        store_uptimes(conn, UPTIMES_TO_STORE);
+        delete(checks.filter(instance_id.eq(4)).filter(up.eq(true)))
+            .execute(conn)
+            .expect("deleting checks of instance ID 4");
+        store_uptimes(conn, UPTIMES_TO_STORE);
+    })
+    .await;
+    assert_eq!(
+        uptimes(&format!("resolution=hour&{range}")).await,
+        rocket::serde::json::json!([
+            { "period": first_hour, "checks": 3, "uptime": 66 },
+            { "period": second_hour, "checks": 2, "uptime": 50 },
+        ])
+    );
+    assert_eq!(
+        uptimes(&range).await,
+        rocket::serde::json::json!([{ "period": day, "checks": 5, "uptime": 60 }])
+    );
+
+    db.run(|conn| {
+        delete(instances.filter(id.eq(4)))
+            .execute(conn)
+            .expect("deleting instance ID 4")
+    })
+    .await;
 }
@@ -0,0 +1,258 @@
+// local web server standing in for PrivateBin instances and the Mozilla Observatory API, so the
+// probing of instances can be tested without network access
+use super::models::CSP_RECOMMENDATION;
+use super::rocket;
+use super::scanners::set_observatory_api;
+use diesel::connection::SimpleConnection;
+use diesel::{Connection, SqliteConnection};
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{CONTENT_SECURITY_POLICY, CONTENT_TYPE, LOCATION, X_CONTENT_TYPE_OPTIONS};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::thread;
+
+const OBSERVATORY_PATH: &str = "/api/v2/scan";
+
+static MOCK_SERVER: OnceLock<MockServer> = OnceLock::new();
+
+/// canned response, served for a path of the mock server
+#[derive(Clone)]
+pub struct Fixture {
+    pub status: StatusCode,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Fixture {
+    /// HTML page in the format of the bootstrap5 template, sent with the recommended CSP
+    #[must_use]
+    pub fn privatebin(version: &str) -> Fixture {
+        Fixture::html(&format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<link type="text/css" rel="stylesheet" href="css/bootstrap5/bootstrap-5.3.3.css" />
+<script type="text/javascript" data-cfasync="false" src="js/privatebin.js?{version}" crossorigin="anonymous"></script>
+</head>
+<body>
+<select id="pasteExpiration" name="pasteExpiration" class="form-select">
+<option value="5min">5 minutes</option>
+<option value="1day">1 day</option>
+<option value="1week" selected="selected">1 week</option>
+</select>
+<input class="form-check-input" type="checkbox" id="burnafterreading" name="burnafterreading" />
+<input class="form-check-input" type="checkbox" id="opendiscussion" name="opendiscussion" />
+<input type="password" id="passwordinput" placeholder="Password (recommended)" class="form-control" size="23" />
+<input type="file" id="attachment" name="attachment" class="form-control" multiple="multiple" />
+<select id="pasteFormatter" name="pasteFormatter" class="form-select">
+<option value="plaintext" selected="selected">Plain Text</option>
+<option value="markdown">Markdown</option>
+</select>
+</body>
+</html>
+"#
+        ))
+        .with_header(CONTENT_SECURITY_POLICY.as_str(), CSP_RECOMMENDATION)
+        .with_header(X_CONTENT_TYPE_OPTIONS.as_str(), "nosniff")
+    }
+
+    /// HTML page of a `ZeroBin` 0.20 instance
+    #[must_use]
+    pub fn zerobin() -> Fixture {
+        Fixture::html(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<link type="text/css" rel="stylesheet" href="css/zerobin.css?0.20" />
+<script src="js/zerobin.js?0.20"></script>
+</head>
+<body>
+<select id="pasteExpiration" name="pasteExpiration">
+<option value="1day">1 day</option>
+<option value="1month" selected="selected">1 month</option>
+</select>
+</body>
+</html>
+"#,
+        )
+        .with_header(CONTENT_SECURITY_POLICY.as_str(), "default-src 'self'")
+    }
+
+    #[must_use]
+    pub fn html(body: &str) -> Fixture {
+        Fixture {
+            status: StatusCode::OK,
+            headers: vec![(
+                CONTENT_TYPE.as_str().into(),
+                "text/html; charset=UTF-8".into(),
+            )],
+            body: body.into(),
+        }
+    }
+
+    /// empty response with the given status, i.e. for errors
+    #[must_use]
+    pub fn empty(status: StatusCode) -> Fixture {
+        Fixture {
+            status,
+            headers: vec![],
+            body: String::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn redirect(location: &str) -> Fixture {
+        Fixture {
+            status: StatusCode::MOVED_PERMANENTLY,
+            headers: vec![(LOCATION.as_str().into(), location.into())],
+            body: String::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn robots(rules: &str) -> Fixture {
+        Fixture {
+            status: StatusCode::OK,
+            headers: vec![(CONTENT_TYPE.as_str().into(), "text/plain".into())],
+            body: rules.into(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_header(mut self, name: &str, value: &str) -> Fixture {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+type Routes = Arc<RwLock<HashMap<String, Fixture>>>;
+
+struct MockServer {
+    address: SocketAddr,
+    routes: Routes,
+}
+
+/// serve the fixture at the given path (without query) of the mock server and return its URL
+///
+/// # Panics
+///
+/// May panic if the mock server fails to start or its routes are poisoned.
+pub fn serve(path: &str, fixture: Fixture) -> String {
+    mock_server()
+        .routes
+        .write()
+        .expect("mock server routes")
+        .insert(path.into(), fixture);
+    mock_url(path)
+}
+
+/// URL of the given path on the mock server, responding with a 404 unless a fixture is served
+///
+/// # Panics
+///
+/// May panic if the mock server fails to start.
+pub fn mock_url(path: &str) -> String {
+    format!("http://{}{path}", mock_server().address)
+}
+
+/// start another mock server, serving only the given fixtures, and return the URL of its first
+/// path - for host wide resources, like the robots.txt, that would affect the other tests
+///
+/// # Panics
+///
+/// May panic if the mock server fails to start or no fixture is given.
+pub fn serve_host(fixtures: Vec<(&str, Fixture)>) -> String {
+    let path = fixtures.first().expect("fixture to serve").0.to_string();
+    let routes = fixtures
+        .into_iter()
+        .map(|(path, fixture)| (path.into(), fixture))
+        .collect();
+    format!("http://{}{path}", start(Arc::new(RwLock::new(routes))))
+}
+
+/// connection to the directory database, set up like the ones of the managed pool, so deleting
+/// instances cascades to their checks, scans and events
+///
+/// # Panics
+///
+/// May panic if the database isn't configured or can't be opened.
+#[must_use]
+pub fn establish_connection() -> SqliteConnection {
+    let directory_config = rocket_sync_db_pools::Config::from("directory", &rocket())
+        .expect("configuration of directory database");
+    let mut conn = SqliteConnection::establish(&directory_config.url)
+        .expect("connection to directory database");
+    conn.batch_execute("PRAGMA busy_timeout = 1000; PRAGMA foreign_keys = ON;")
+        .expect("enabling foreign key constraints");
+    conn
+}
+
+// starts the mock server on first use and points the observatory scanner to it
+fn mock_server() -> &'static MockServer {
+    MOCK_SERVER.get_or_init(|| {
+        let routes = Arc::new(RwLock::new(HashMap::from([(
+            OBSERVATORY_PATH.into(),
+            Fixture {
+                status: StatusCode::OK,
+                headers: vec![(CONTENT_TYPE.as_str().into(), "application/json".into())],
+                body: r#"{"grade":"A+","status_code":200}"#.into(),
+            },
+        )])));
+        let address = start(Arc::clone(&routes));
+        set_observatory_api(format!("http://{address}{OBSERVATORY_PATH}?host="));
+        MockServer { address, routes }
+    })
+}
+
+// serves the routes in a thread of its own, so it keeps serving regardless of the runtimes of
+// the individual tests
+fn start(routes: Routes) -> SocketAddr {
+    let listener =
+        std::net::TcpListener::bind("127.0.0.1:0").expect("binding mock server to a port");
+    listener
+        .set_nonblocking(true)
+        .expect("non-blocking mock server socket");
+    let address = listener.local_addr().expect("address of mock server");
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("runtime of mock server");
+        runtime.block_on(async move {
+            let listener =
+                tokio::net::TcpListener::from_std(listener).expect("listener of mock server");
+            while let Ok((stream, _)) = listener.accept().await {
+                let routes = Arc::clone(&routes);
+                tokio::spawn(http1::Builder::new().serve_connection(
+                    TokioIo::new(stream),
+                    service_fn(move |request| {
+                        std::future::ready(Ok::<_, Infallible>(respond(&routes, &request)))
+                    }),
+                ));
+            }
+        });
+    });
+    address
+}
+
+fn respond(routes: &Routes, request: &Request<Incoming>) -> Response<Full<Bytes>> {
+    let fixture = routes
+        .read()
+        .ok()
+        .and_then(|routes| routes.get(request.uri().path()).cloned())
+        .unwrap_or(Fixture::empty(StatusCode::NOT_FOUND));
+    let mut response = Response::builder().status(fixture.status);
+    for (name, value) in fixture.headers {
+        response = response.header(name, value);
+    }
+    response
+        .body(Full::from(fixture.body))
+        .unwrap_or_else(|_| Response::new(Full::default()))
+}
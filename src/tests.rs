@@ -1,4 +1,6 @@
 use super::rocket;
+use super::test_support::{Fixture, establish_connection, serve};
+use rocket::http::Accept;
 use rocket::http::ContentType;
 use rocket::http::Status;
 use rocket::local::blocking::Client;
@@ -19,10 +21,29 @@ fn index() {
 
 #[test]
 fn about() {
+    use super::functions::rocket_with;
+
     let client = Client::untracked(rocket()).expect("valid rocket instance");
     let response = client.get("/about").dispatch();
     assert_eq!(response.status(), Status::Ok);
     assert!(response.into_string().is_some_and(|s| s.contains("About")));
+
+    // the documented intervals and limits follow the configuration
+    let figment = rocket::Config::figment()
+        .merge(("directory.cron_interval", 600))
+        .merge(("directory.checks_to_store", 144))
+        .merge(("directory.uptimes_to_store", 90))
+        .merge(("directory.api_max_top", 50));
+    let client = Client::untracked(rocket_with(figment)).expect("valid rocket instance");
+    let body = client
+        .get("/about")
+        .dispatch()
+        .into_string()
+        .expect("response body");
+    assert!(body.contains("checked every 10 minutes."));
+    assert!(body.contains("Only the last 144 results (1 day) are kept"));
+    assert!(body.contains("per hour and per day and kept for 90 days."));
+    assert!(body.contains("Pages hold up to 50 instances"));
 }
 
 #[test]
@@ -59,7 +80,7 @@ fn add_post_error() {
         .dispatch();
     assert_eq!(response.status(), Status::Ok);
     assert!(response.into_string().is_some_and(|s| s.contains(
-        "Error adding URL privatebin.info, due to a failed scan within the last 5 minutes."
+        "Error adding URL privatebin.info, due to a failed scan within the last 1 second."
     )));
 
     sleep(Duration::from_secs(2));
@@ -78,16 +99,16 @@ fn add_post_error() {
 
 #[test]
 fn add_post_success() {
+    let url = serve("/add", Fixture::privatebin("2.0.1"));
     let client = Client::untracked(rocket()).expect("valid rocket instance");
     let response = client
         .post("/add")
-        .body("url=https://privatebin.net")
+        .body(format!("url={url}"))
         .header(ContentType::Form)
         .dispatch();
     assert_eq!(response.status(), Status::Ok);
-    assert!(response
-        .into_string()
-        .is_some_and(|s| s.contains("Successfully added URL: https:&#x2F;&#x2F;privatebin.net")));
+    let success = format!("Successfully added URL: {}", url.replace('/', "&#x2F;"));
+    assert!(response.into_string().is_some_and(|s| s.contains(&success)));
 }
 
 #[test]
@@ -124,7 +145,7 @@ fn check_post_error() {
         .dispatch();
     assert_eq!(response.status(), Status::Ok);
     assert!(response.into_string().is_some_and(|s| s.contains(
-        "Error scanning URL privatebin.info, due to a failed scan within the last 5 minutes."
+        "Error scanning URL privatebin.info, due to a failed scan within the last 1 second."
     )));
 
     sleep(Duration::from_secs(2));
@@ -143,18 +164,420 @@ fn check_post_error() {
 
 #[test]
 fn check_post_success() {
+    let url = serve("/check", Fixture::privatebin("2.0.1"));
     let client = Client::untracked(rocket()).expect("valid rocket instance");
     let response = client
         .post("/check")
-        .body("url=https://privatebin.net")
+        .body(format!("url={url}"))
         .header(ContentType::Form)
         .dispatch();
     assert_eq!(response.status(), Status::Ok);
+    let results = format!("Results of checking {}", url.replace('/', "&#x2F;"));
+    assert!(response.into_string().is_some_and(|s| s.contains(&results)));
+}
+
+#[test]
+fn check_post_events() {
+    use super::functions::set_instance_status;
+    use super::models::InstanceStatus;
+    use super::schema::{instance_events, instances};
+    use diesel::prelude::*;
+
+    let mut conn = establish_connection();
+    let url = serve("/check-events", Fixture::privatebin("2.0.1"));
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    client
+        .post("/add")
+        .body(format!("url={url}"))
+        .header(ContentType::Form)
+        .dispatch();
+    let instance_id: i32 = instances::table
+        .select(instances::id)
+        .filter(instances::url.eq(&url))
+        .first(&mut conn)
+        .expect("selecting the added instance");
+    diesel::insert_into(instance_events::table)
+        .values((
+            instance_events::field.eq("version"),
+            instance_events::old_value.eq("1.7.6"),
+            instance_events::new_value.eq("2.0.1"),
+            instance_events::instance_id.eq(instance_id),
+        ))
+        .execute(&mut conn)
+        .expect("inserting an event");
+
+    // the results of checking a listed instance include its history
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    let response = client
+        .post("/check")
+        .body(format!("url={url}"))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body = response.into_string().expect("response body");
+    assert!(body.contains("<td>1.7.6</td>") && body.contains("<td>2.0.1</td>"));
+
+    // no event gets recorded for instances that couldn't be updated
+    assert!(
+        set_instance_status(
+            &mut conn,
+            0,
+            InstanceStatus::Active,
+            InstanceStatus::Suspended
+        )
+        .is_err()
+    );
+    let orphaned: i64 = instance_events::table
+        .filter(instance_events::instance_id.eq(0))
+        .count()
+        .get_result(&mut conn)
+        .expect("counting events of a missing instance");
+    assert_eq!(orphaned, 0);
+
+    diesel::delete(instances::table.filter(instances::id.eq(instance_id)))
+        .execute(&mut conn)
+        .expect("deleting the added instance");
+}
+
+#[test]
+fn operator() {
+    use super::models::{InstanceFeatures, InstanceNew, OWNERSHIP_PATH, sha256_hex};
+    use super::schema::instances;
+    use diesel::prelude::*;
+
+    let mut conn = establish_connection();
+    let url = serve("/operator", Fixture::privatebin("2.0.1"));
+    serve(
+        OWNERSHIP_PATH,
+        Fixture::robots(&format!("# other hashes\n{}\n", sha256_hex("secret"))),
+    );
+    diesel::insert_into(instances::table)
+        .values(InstanceNew {
+            id: None,
+            url: url.clone(),
+            version: "2.0.1".into(),
+            https: false,
+            https_redirect: false,
+            country_id: "AQ".into(),
+            attachments: true,
+            csp_header: true,
+            csp_policy: String::new(),
+            features: InstanceFeatures::default(),
+        })
+        .execute(&mut conn)
+        .expect("inserting instance");
+
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    let response = client.get("/operator").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let response = client
+        .post("/operator")
+        .body(format!(
+            "url={url}&secret=guessed&display_name=Test&description=&contact="
+        ))
+        .header(ContentType::Form)
+        .dispatch();
+    assert!(
+        response
+            .into_string()
+            .is_some_and(|s| s.contains("The secret doesn&#x27;t match any hash"))
+    );
+
+    let response = client
+        .post("/operator")
+        .body(format!(
+            "url={url}&secret=secret&display_name=Test&description=&contact=ops%40example.com&delisted=true"
+        ))
+        .header(ContentType::Form)
+        .dispatch();
+    assert!(
+        response
+            .into_string()
+            .is_some_and(|s| s.contains("it will no longer be listed"))
+    );
+    let (verified, delisted, display_name): (bool, bool, String) = instances::table
+        .select((
+            instances::verified,
+            instances::delisted,
+            instances::display_name,
+        ))
+        .filter(instances::url.eq(&url))
+        .first(&mut conn)
+        .expect("selecting operator settings of instance");
+    assert!(verified && delisted);
+    assert_eq!(display_name, "Test");
+
+    diesel::delete(instances::table.filter(instances::url.eq(&url)))
+        .execute(&mut conn)
+        .expect("deleting instance");
+}
+
+#[test]
+#[allow(clippy::too_many_lines)]
+fn admin() {
+    use super::functions::rocket_with;
+    use super::models::sha256_hex;
+    use super::schema::{audit_log, blocklist, instances};
+    use diesel::prelude::*;
+    use rocket::http::Cookie;
+
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    let response = client.get("/admin").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+
+    let mut conn = establish_connection();
+    let figment =
+        rocket::Config::figment().merge(("directory.admin_token", "correct-horse-battery"));
+    let client = Client::tracked(rocket_with(figment)).expect("valid rocket instance");
+    let response = client.get("/admin").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(
+        response
+            .into_string()
+            .is_some_and(|s| s.contains("Admin token"))
+    );
+
+    // actions require a login, cookies of sessions the service didn't start don't count
+    let actions = [
+        ("/admin/instance/1", "notes=&pinned=true&hidden=false"),
+        ("/admin/instance/1/rescan", ""),
+        ("/admin/instance/1/delete", ""),
+        ("/admin/blocklist", "domain=blocked.example&reason=spam"),
+        ("/admin/blocklist/1/delete", ""),
+    ];
+    for (uri, body) in actions {
+        let response = client
+            .post(uri)
+            .body(body)
+            .header(ContentType::Form)
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized, "status of {uri}");
+        let response = client
+            .post(uri)
+            .body(body)
+            .header(ContentType::Form)
+            .cookie(Cookie::new("admin", sha256_hex("correct-horse-battery")))
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized, "status of {uri}");
+        let response = client
+            .post(uri)
+            .body(body)
+            .header(ContentType::Form)
+            .cookie(Cookie::new("admin", sha256_hex("guessed")))
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized, "status of {uri}");
+    }
+
+    client
+        .post("/admin/login")
+        .body("token=guessed")
+        .header(ContentType::Form)
+        .dispatch();
+    let response = client.get("/admin").dispatch();
     assert!(
         response
             .into_string()
-            .is_some_and(|s| s.contains("Results of checking https:&#x2F;&#x2F;privatebin.net"))
+            .is_some_and(|s| s.contains("Invalid admin token."))
     );
+
+    client
+        .post("/admin/login")
+        .body("token=correct-horse-battery")
+        .header(ContentType::Form)
+        .dispatch();
+    let response = client
+        .post("/admin/blocklist")
+        .body("domain=*.Blocked.example&reason=spam")
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    let response = client.get("/admin").dispatch();
+    assert!(
+        response
+            .into_string()
+            .is_some_and(|s| s.contains("Domain blocked.example blocked, 0 instances deleted."))
+    );
+    let reason: String = audit_log::table
+        .select(audit_log::details)
+        .filter(audit_log::action.eq("block"))
+        .filter(audit_log::target.eq("blocked.example"))
+        .first(&mut conn)
+        .expect("selecting audit entry of the blocked domain");
+    assert_eq!(reason, "spam");
+
+    let response = client
+        .post("/add")
+        .body("url=https://paste.blocked.example")
+        .header(ContentType::Form)
+        .dispatch();
+    assert!(
+        response
+            .into_string()
+            .is_some_and(|s| s.contains("is blocked from the directory"))
+    );
+
+    let audit_details = |conn: &mut SqliteConnection, action: &str, target: &str| {
+        audit_log::table
+            .select(audit_log::details)
+            .filter(audit_log::action.eq(action))
+            .filter(audit_log::target.eq(target))
+            .order(audit_log::id.desc())
+            .first::<String>(conn)
+            .unwrap_or_else(|_| panic!("selecting audit entry of {action} {target}"))
+    };
+    let flash = |uri: &str, body: &str| -> String {
+        let response = client
+            .post(uri)
+            .body(body)
+            .header(ContentType::Form)
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther, "status of {uri}");
+        client
+            .get("/admin")
+            .dispatch()
+            .into_string()
+            .expect("response body")
+    };
+
+    let url = serve("/admin-moderated", Fixture::privatebin("2.0.1"));
+    client
+        .post("/add")
+        .body(format!("url={url}"))
+        .header(ContentType::Form)
+        .dispatch();
+    let instance_id: i32 = instances::table
+        .select(instances::id)
+        .filter(instances::url.eq(&url))
+        .first(&mut conn)
+        .expect("selecting the added instance");
+    let escaped_url = url.replace('/', "&#x2F;");
+
+    let body = flash(
+        &format!("/admin/instance/{instance_id}"),
+        "notes=known+spammer&pinned=true&hidden=false",
+    );
+    assert!(body.contains(&format!("Instance {escaped_url} updated.")));
+    let (pinned, notes): (bool, String) = instances::table
+        .select((instances::pinned, instances::notes))
+        .filter(instances::id.eq(instance_id))
+        .first(&mut conn)
+        .expect("selecting the moderated instance");
+    assert!(pinned);
+    assert_eq!(notes, "known spammer");
+    assert_eq!(
+        audit_details(&mut conn, "moderate", &url),
+        "pinned: true, hidden: false, notes: known spammer"
+    );
+
+    flash(&format!("/admin/instance/{instance_id}/rescan"), "");
+    assert!(audit_details(&mut conn, "rescan", &url).contains(&url));
+
+    let body = flash(&format!("/admin/instance/{instance_id}/delete"), "");
+    assert!(body.contains(&format!("Instance {escaped_url} deleted.")));
+    let remaining: i64 = instances::table
+        .filter(instances::id.eq(instance_id))
+        .count()
+        .get_result(&mut conn)
+        .expect("counting the deleted instance");
+    assert_eq!(remaining, 0);
+    assert_eq!(audit_details(&mut conn, "delete", &url), "");
+    // actions on missing instances fail
+    let body = flash(&format!("/admin/instance/{instance_id}/rescan"), "");
+    assert!(body.contains("Instance not found."));
+
+    let domain_id: i32 = blocklist::table
+        .select(blocklist::id)
+        .filter(blocklist::domain.eq("blocked.example"))
+        .first(&mut conn)
+        .expect("selecting the blocked domain");
+    let body = flash(&format!("/admin/blocklist/{domain_id}/delete"), "");
+    assert!(body.contains("Domain blocked.example unblocked."));
+    assert_eq!(audit_details(&mut conn, "unblock", "blocked.example"), "");
+
+    let session = client
+        .cookies()
+        .get("admin")
+        .map(|cookie| cookie.value().to_string())
+        .expect("session cookie");
+    client.post("/admin/logout").dispatch();
+    let response = client.get("/admin").dispatch();
+    assert!(
+        response
+            .into_string()
+            .is_some_and(|s| s.contains("Admin token"))
+    );
+    // the session ended, its ID no longer works
+    let response = client
+        .post("/admin/instance/1/rescan")
+        .cookie(Cookie::new("admin", session))
+        .dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    diesel::delete(blocklist::table)
+        .execute(&mut conn)
+        .expect("clearing blocklist");
+}
+
+#[test]
+fn instance_certificate() {
+    use super::functions::get_epoch;
+    use super::schema::{instances, scans};
+    use diesel::prelude::*;
+
+    let mut conn = establish_connection();
+    let url = serve("/instance-certificate", Fixture::privatebin("2.0.1"));
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    client
+        .post("/add")
+        .body(format!("url={url}"))
+        .header(ContentType::Form)
+        .dispatch();
+    let instance_id: i32 = instances::table
+        .select(instances::id)
+        .filter(instances::url.eq(&url))
+        .first(&mut conn)
+        .expect("selecting the added instance");
+    // each client has a cache of its own, so it sees the changed scan
+    let mut certificate = |not_after: i64| -> String {
+        diesel::delete(
+            scans::table
+                .filter(scans::instance_id.eq(instance_id))
+                .filter(scans::scanner.eq("tls")),
+        )
+        .execute(&mut conn)
+        .expect("deleting the TLS scan");
+        diesel::insert_into(scans::table)
+            .values((
+                scans::scanner.eq("tls"),
+                scans::rating.eq("F"),
+                scans::percent.eq(0),
+                scans::instance_id.eq(instance_id),
+                scans::details.eq(format!(r#"{{"not_after":{not_after}}}"#)),
+            ))
+            .execute(&mut conn)
+            .expect("inserting a TLS scan");
+        let client = Client::untracked(rocket()).expect("valid rocket instance");
+        let response = client.get(format!("/instance/{instance_id}")).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        response.into_string().expect("response body")
+    };
+
+    // a failed handshake doesn't report an expiry
+    assert!(!certificate(0).contains("The certificate expire"));
+    let expired = i64::try_from(get_epoch()).expect("current time") - 10 * 86400 - 3600;
+    assert!(certificate(expired).contains("The certificate expired <em>10 days</em> ago."));
+
+    diesel::delete(instances::table.filter(instances::id.eq(instance_id)))
+        .execute(&mut conn)
+        .expect("deleting the added instance");
+}
+
+#[test]
+fn instance_missing() {
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    let response = client.get("/instance/0").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
 }
 
 #[test]
@@ -163,4 +586,470 @@ fn forward_me() {
     let response = client.get("/forward-me").dispatch();
     assert_eq!(response.status(), Status::SeeOther);
     assert!(response.headers().contains("Location"));
+
+    let response = client.get("/forward-me?template=bootstrap5").dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    assert!(response.headers().contains("Location"));
+
+    // as documented on the about page, browsers percent-encode the comparison operators, while
+    // Rocket rejects them unencoded
+    let response = client
+        .get("/forward-me?version%3E=1.7&version%3C=2.0")
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    assert!(response.headers().contains("Location"));
+
+    // other comparison operators would otherwise get silently ignored
+    for query in ["version%3E1.7", "version%3C1.7", "country%3E=DE"] {
+        let response = client.get(format!("/forward-me?{query}")).dispatch();
+        assert_eq!(response.status(), Status::BadRequest, "{query}");
+    }
+}
+
+#[test]
+fn api() {
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    let response = client
+        .get("/api?top=100&max_latency_ms=500")
+        .header(Accept::JSON)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(response.into_string().is_some_and(|s| s.starts_with('[')));
+
+    let response = client
+        .get("/api?discussion=true&password=false&max_expiry=1week&template=bootstrap")
+        .header(Accept::JSON)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(response.into_string().is_some_and(|s| s.starts_with('[')));
+}
+
+#[test]
+fn api_check() {
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    let response = client
+        .get("/api/check?url=privatebin.example")
+        .header(Accept::JSON)
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+    assert!(
+        response
+            .into_string()
+            .is_some_and(|s| s.contains(r#""error":"invalid_url""#))
+    );
+
+    let url = serve(
+        "/api-check-homepage",
+        Fixture::html("<p>Not a PrivateBin instance.</p>"),
+    );
+    let response = client
+        .get(format!("/api/check?url={url}"))
+        .header(Accept::JSON)
+        .dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    assert!(
+        response
+            .into_string()
+            .is_some_and(|s| s.contains(r#""error":"not_privatebin""#))
+    );
+
+    let url = serve("/api-check", Fixture::privatebin("2.0.1"));
+    let response = client
+        .get(format!("/api/check?url={url}"))
+        .header(Accept::JSON)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(
+        response
+            .into_string()
+            .is_some_and(|s| s.contains(r#""version":"2.0.1""#))
+    );
+}
+
+#[test]
+#[allow(clippy::too_many_lines)]
+fn api_openapi() {
+    use super::models::InstanceFilter;
+    use super::schema::instances;
+    use diesel::prelude::*;
+    use rocket::serde::json::{Value, from_str, to_value};
+    use std::collections::BTreeSet;
+
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    let response = client.get("/api/openapi.json").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let document: Value =
+        from_str(&response.into_string().expect("OpenAPI document")).expect("valid JSON");
+
+    // every JSON route is described, along with all of its parameters
+    let filter_fields: Vec<String> = to_value(InstanceFilter::default())
+        .expect("serialized filter")
+        .as_object()
+        .map(|fields| fields.keys().cloned().collect())
+        .unwrap_or_default();
+    let api_routes: Vec<_> = client
+        .rocket()
+        .routes()
+        .filter(|route| route.uri.path().starts_with("/api"))
+        .collect();
+    assert_eq!(
+        api_routes.len(),
+        document["paths"]
+            .as_object()
+            .map_or(0, rocket::serde::json::serde_json::Map::len)
+    );
+    for route in api_routes {
+        let path = route.uri.path().replace('<', "{").replace('>', "}");
+        let operation = &document["paths"][&path]["get"];
+        assert!(operation.is_object(), "route {path} is not described");
+        let mut parameters: BTreeSet<String> = route
+            .uri
+            .path()
+            .split('/')
+            .chain(
+                route
+                    .uri
+                    .query()
+                    .map(|query| query.split('&'))
+                    .into_iter()
+                    .flatten(),
+            )
+            .filter_map(|segment| segment.strip_prefix('<')?.strip_suffix('>'))
+            .flat_map(|name| {
+                // trailing parameters collect the fields of the instance filter
+                if name.ends_with("..") {
+                    filter_fields.clone()
+                } else {
+                    vec![name.to_string()]
+                }
+            })
+            .collect();
+        for parameter in operation["parameters"].as_array().into_iter().flatten() {
+            let name = parameter["name"].as_str().unwrap_or_default();
+            assert!(
+                parameters.remove(name),
+                "parameter {name} of route {path} doesn't exist"
+            );
+        }
+        assert!(
+            parameters.is_empty(),
+            "parameters {parameters:?} of route {path} are not described"
+        );
+    }
+
+    // real responses match the described schemas
+    let url = serve("/api-openapi", Fixture::privatebin("2.0.1"));
+    client
+        .post("/add")
+        .body(format!("url={url}"))
+        .header(ContentType::Form)
+        .dispatch();
+    let mut conn = establish_connection();
+    let instance_id: i32 = instances::table
+        .select(instances::id)
+        .filter(instances::url.eq(&url))
+        .first(&mut conn)
+        .expect("selecting the added instance");
+    for (uri, path, status) in [
+        ("/api?top=100".to_string(), "/api", Status::Ok),
+        (
+            "/api/v2/instances?sort=version&https=false".to_string(),
+            "/api/v2/instances",
+            Status::Ok,
+        ),
+        (format!("/api/check?url={url}"), "/api/check", Status::Ok),
+        (
+            "/api/check?url=privatebin.example".to_string(),
+            "/api/check",
+            Status::BadRequest,
+        ),
+        (
+            format!("/api/instance/{instance_id}/uptime?resolution=hour"),
+            "/api/instance/{instance}/uptime",
+            Status::Ok,
+        ),
+        (
+            format!("/api/instance/{instance_id}/events"),
+            "/api/instance/{instance}/events",
+            Status::Ok,
+        ),
+    ] {
+        let response = client.get(&uri).header(Accept::JSON).dispatch();
+        assert_eq!(response.status(), status, "status of {uri}");
+        let body: Value =
+            from_str(&response.into_string().expect("response body")).expect("valid JSON");
+        let schema = &document["paths"][path]["get"]["responses"][status.code.to_string()]["content"]
+            ["application/json"]["schema"];
+        if let Err(e) = validate_schema(&body, schema, &document) {
+            panic!("response of {uri} doesn't match the schema: {e}");
+        }
+        if uri.starts_with("/api?") {
+            assert!(body.as_array().is_some_and(|list| !list.is_empty()));
+        }
+    }
+
+    diesel::delete(instances::table.filter(instances::id.eq(instance_id)))
+        .execute(&mut conn)
+        .expect("deleting the added instance");
+}
+
+// checks the subset of the JSON schema keywords used in the OpenAPI document
+fn validate_schema(
+    value: &rocket::serde::json::Value,
+    schema: &rocket::serde::json::Value,
+    document: &rocket::serde::json::Value,
+) -> Result<(), String> {
+    use rocket::serde::json::Value;
+
+    if let Some(reference) = schema["$ref"].as_str() {
+        let name = reference.trim_start_matches("#/components/schemas/");
+        return validate_schema(value, &document["components"]["schemas"][name], document);
+    }
+    if value.is_null() {
+        return if schema["nullable"] == Value::Bool(true) {
+            Ok(())
+        } else {
+            Err(format!("null isn't allowed by {schema}"))
+        };
+    }
+    let matches_type = match schema["type"].as_str() {
+        Some("object") => value.is_object(),
+        Some("array") => value.is_array(),
+        Some("string") => value.is_string(),
+        Some("integer") => value.is_i64() || value.is_u64(),
+        Some("number") => value.is_number(),
+        Some("boolean") => value.is_boolean(),
+        _ => return Err(format!("unsupported schema {schema}")),
+    };
+    if !matches_type {
+        return Err(format!("{value} isn't of type {}", schema["type"]));
+    }
+    if let Some(allowed) = schema["enum"].as_array()
+        && !allowed.contains(value)
+    {
+        return Err(format!("{value} isn't one of {}", schema["enum"]));
+    }
+    if let Some(items) = value.as_array() {
+        for item in items {
+            validate_schema(item, &schema["items"], document)?;
+        }
+    }
+    if let Some(object) = value.as_object() {
+        for required in schema["required"].as_array().into_iter().flatten() {
+            let required = required.as_str().unwrap_or_default();
+            if !object.contains_key(required) {
+                return Err(format!("property {required} is missing"));
+            }
+        }
+        for (key, property) in object {
+            match (&schema["properties"][key], &schema["additionalProperties"]) {
+                (Value::Null, Value::Bool(false)) => {
+                    return Err(format!("property {key} isn't described"));
+                }
+                (Value::Null, Value::Null | Value::Bool(true)) => {}
+                (Value::Null, additional) => validate_schema(property, additional, document)?,
+                (described, _) => validate_schema(property, described, document)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn api_v2_instances() {
+    use rocket::serde::json::{Value, from_str};
+
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    for path in ["/api-v2-a", "/api-v2-b", "/api-v2-c"] {
+        let url = serve(path, Fixture::privatebin("2.0.1"));
+        client
+            .post("/add")
+            .body(format!("url={url}"))
+            .header(ContentType::Form)
+            .dispatch();
+    }
+    let mut urls = vec![];
+    let mut uri = "/api/v2/instances?version=2.0&limit=2&order=desc".to_string();
+    loop {
+        let response = client.get(&uri).header(Accept::JSON).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let page: Value =
+            from_str(&response.into_string().expect("response body")).expect("valid JSON");
+        assert_eq!(page["filter"]["version"], "2.0");
+        assert_eq!(page["sort"], "url");
+        assert!(page["total"].as_u64().is_some_and(|total| total >= 3));
+        urls.extend(
+            page["instances"]
+                .as_array()
+                .expect("instances of the page")
+                .iter()
+                .map(|instance| instance["url"].as_str().unwrap_or_default().to_string()),
+        );
+        if let Some(next) = page["next"].as_str() {
+            assert!(next.contains("version=2.0") && next.contains("limit=2"));
+            uri = format!("/api/v2/instances{next}");
+        } else {
+            assert_eq!(page["total"].as_u64(), u64::try_from(urls.len()).ok());
+            break;
+        }
+    }
+    // all instances got returned, once each and in descending order
+    let mut sorted = urls.clone();
+    sorted.sort();
+    sorted.reverse();
+    sorted.dedup();
+    assert_eq!(urls, sorted);
+
+    let response = client
+        .get("/api/v2/instances?version=0.&sort=uptime")
+        .header(Accept::JSON)
+        .dispatch();
+    assert!(
+        response
+            .into_string()
+            .is_some_and(|s| s.contains(r#""total":0"#) && s.contains(r#""next":null"#))
+    );
+}
+
+#[test]
+fn api_filters() {
+    use rocket::serde::json::{Value, from_str};
+
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    let mut urls = vec![];
+    for path in ["/api-filters-a", "/api-filters-b"] {
+        let url = serve(path, Fixture::privatebin("2.0.1"));
+        client
+            .post("/add")
+            .body(format!("url={url}"))
+            .header(ContentType::Form)
+            .dispatch();
+        urls.push(url);
+    }
+    let total = |query: &str| -> u64 {
+        let response = client
+            .get(format!("/api/v2/instances?{query}"))
+            .header(Accept::JSON)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let page: Value =
+            from_str(&response.into_string().expect("response body")).expect("valid JSON");
+        page["total"].as_u64().expect("total of the page")
+    };
+
+    // instances of the local test server can't be located and end up in the unknown region
+    let all = total("version=2.0.1");
+    assert!(all >= 2);
+    assert_eq!(total("version=2.0.1&country=DE,aq"), all);
+    assert_eq!(total("version=2.0.1&country!=AQ"), 0);
+    assert_eq!(total("version=2.0.1&region=europe,unknown"), all);
+    assert_eq!(total("version=2.0.1&region!=Unknown"), 0);
+    assert_eq!(total("version=2.0.1&region=antarctica"), 0);
+    assert_eq!(total("version=2.0.1&region=EU,five-eyes"), 0);
+    assert_eq!(total("version=2.0.1&region!=eea"), all);
+    assert_eq!(total("version=1.,2.0"), all);
+    assert_eq!(total("version=2.0.1&version!=2."), 0);
+    assert_eq!(total("version%3E=2.0&version%3C=2.0.1&version=2.0.1"), all);
+    assert_eq!(total("version%3E=1.7&version=2.0.1"), all);
+    assert_eq!(total("version%3E=2.1&version=2.0.1"), 0);
+    assert_eq!(total("version%3C=2.0&version=2.0.1"), all);
+    assert_eq!(total("version%3C=1.7&version=2.0.1"), 0);
+    assert_eq!(
+        total(&format!("version=2.0.1&exclude={},{}", urls[0], urls[1])),
+        all - 2
+    );
+    assert_eq!(
+        total(&format!(
+            "version=2.0.1&exclude={}&exclude={}",
+            urls[0], urls[1]
+        )),
+        all - 2
+    );
+
+    let response = client
+        .get("/api/v2/instances?version=2.0.1&limit=1")
+        .header(Accept::JSON)
+        .dispatch();
+    let page: Value =
+        from_str(&response.into_string().expect("response body")).expect("valid JSON");
+    assert_eq!(page["instances"][0]["region"], "");
+    assert_eq!(page["instances"][0]["jurisdiction"], Value::Array(vec![]));
+
+    let response = client.get("/?group=region").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body = response.into_string().expect("response body");
+    assert!(body.contains("Unknown region") && !body.contains("Version 2.0"));
+
+    let response = client
+        .get("/forward-me?region=europe&country!=AQ")
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(
+        response.headers().get_one("Location"),
+        Some("https://privatebin.net")
+    );
+}
+
+#[test]
+fn index_filter() {
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    for path in ["/index-filter-a", "/index-filter-b"] {
+        let url = serve(path, Fixture::privatebin("2.0.1"));
+        client
+            .post("/add")
+            .body(format!("url={url}"))
+            .header(ContentType::Form)
+            .dispatch();
+    }
+    let page = |query: &str| -> String {
+        let response = client.get(format!("/?{query}")).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        response.into_string().expect("response body")
+    };
+
+    let body = page("version=2.0.1&sort=url&order=desc");
+    // slashes get escaped in the HTML
+    let (a, b) = (
+        body.find("index-filter-a").expect("first instance listed"),
+        body.find("index-filter-b").expect("second instance listed"),
+    );
+    assert!(b < a, "instances sorted by descending URL");
+    assert!(body.contains("Address ▼"));
+    assert!(body.contains(r#"value="2.0.1""#));
+    // sort links and the reset keep the other parameters
+    assert!(body.contains("?version=2.0.1&amp;sort=uptime&amp;order=desc"));
+    assert!(body.contains("?version=2.0.1&amp;sort=url&amp;order=asc"));
+    assert!(body.contains(r#"href="?sort=url&amp;order=desc""#));
+    assert!(body.contains("matching the filter"));
+
+    // blank form fields don't filter
+    assert!(page("country=&region=&min_uptime=&https=&version=2.0.1").contains("index-filter-a"));
+    // the flags can exclude instances and keep doing so when the form gets sent again
+    let body = page("version=2.0.1&https=false");
+    assert!(body.contains("index-filter-a"));
+    assert!(body.contains(r#"<option value="false" selected>no</option>"#));
+    assert!(!body.contains(r#"<option value="true" selected>"#));
+    assert!(page("version=2.0.1&country!=AQ").contains("No instances match the filter."));
+    let body = page("group=region&region=unknown&https=true");
+    assert!(!body.contains("index-filter-a") && body.contains(r#"name="group" value="region""#));
+}
+
+#[test]
+fn api_uptime() {
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    let response = client
+        .get("/api/instance/0/uptime?resolution=hour")
+        .header(Accept::JSON)
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn api_events() {
+    let client = Client::untracked(rocket()).expect("valid rocket instance");
+    let response = client
+        .get("/api/instance/0/events?field=version")
+        .header(Accept::JSON)
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
 }